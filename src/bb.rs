@@ -3,18 +3,27 @@
 use super::*;
 use super::c_api::*;
 
-/// A wrapper around a `LLVMBasicBlockRef`
+/// A wrapper around a `LLVMBasicBlockRef` for a specific context
+///
+/// Basic blocks borrow the module of their function and cannot outlive it.
 #[derive(Copy, Clone)]
-pub struct BasicBlock {
-    pub(crate) basic_block: LLVMBasicBlockRef
+pub struct BasicBlock<'ctx> {
+    pub(crate) basic_block: LLVMBasicBlockRef,
+    pub(crate) marker: PhantomData<&'ctx Context>,
 }
 
-impl BasicBlock {
+impl<'ctx> BasicBlock<'ctx> {
     /// Delete this basic block
-    pub fn delete(self) {
-        unsafe {
-            LLVMDeleteBasicBlock(self.basic_block)
-        }
+    ///
+    /// Use `Module::delete_block` to have the requirements below checked.
+    ///
+    /// # Safety
+    ///
+    /// The block must not be branched to, and its instructions must not be used by other blocks.
+    /// Any other copies of this handle, and of handles to instructions in the block, must not be
+    /// used afterwards.
+    pub unsafe fn delete(self) {
+        LLVMDeleteBasicBlock(self.basic_block)
     }
 
     /// Get the name of a basic block
@@ -30,7 +39,7 @@ impl BasicBlock {
     }
 }
 
-impl Deref for BasicBlock {
+impl<'ctx> Deref for BasicBlock<'ctx> {
     type Target = LLVMBasicBlockRef;
 
     fn deref(&self) -> &LLVMBasicBlockRef {
@@ -38,7 +47,7 @@ impl Deref for BasicBlock {
     }
 }
 
-impl Debug for BasicBlock {
+impl<'ctx> Debug for BasicBlock<'ctx> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         if let Some(name) = self.get_name() {
             write!(f, "BasicBlock({})", name)
@@ -0,0 +1,64 @@
+//! A wrapper around a `LLVMMemoryBufferRef`
+
+use super::*;
+use super::c_api::*;
+
+use llvm_sys::prelude::LLVMMemoryBufferRef;
+use std::slice;
+
+/// A wrapper around a `LLVMMemoryBufferRef`
+///
+/// The buffer is disposed when it leaves scope unless it is passed to a function that takes
+/// ownership of it with `into_inner`.
+pub(crate) struct MemoryBuffer {
+    buffer: Option<LLVMMemoryBufferRef>,
+}
+
+impl MemoryBuffer {
+    /// Copies the given bytes into a new buffer with a name used in diagnostics
+    pub fn from_bytes<S>(name: S, bytes: &[u8]) -> Result<MemoryBuffer, Error> where S: AsRef<str> {
+        let name = into_c(name)?;
+        Ok(MemoryBuffer {
+            buffer: Some(
+                unsafe {
+                    LLVMCreateMemoryBufferWithMemoryRangeCopy(bytes.as_ptr() as *const i8, bytes.len(), name.as_ptr())
+                }
+            ),
+        })
+    }
+
+    /// Takes ownership of a buffer created by LLVM
+    pub unsafe fn from_inner(buffer: LLVMMemoryBufferRef) -> MemoryBuffer {
+        MemoryBuffer {
+            buffer: Some(buffer),
+        }
+    }
+
+    /// Returns the contents of the buffer
+    pub fn as_bytes(&self) -> &[u8] {
+        unsafe {
+            let buffer = self.buffer.unwrap();
+            slice::from_raw_parts(LLVMGetBufferStart(buffer) as *const u8, LLVMGetBufferSize(buffer))
+        }
+    }
+
+    /// Returns the internal buffer reference
+    pub fn inner(&self) -> LLVMMemoryBufferRef {
+        self.buffer.unwrap()
+    }
+
+    /// Destroys the wrapper, returning the internal buffer reference
+    pub unsafe fn into_inner(mut self) -> LLVMMemoryBufferRef {
+        self.buffer.take().unwrap()
+    }
+}
+
+impl Drop for MemoryBuffer {
+    fn drop(&mut self) {
+        if let Some(buffer) = self.buffer {
+            unsafe {
+                LLVMDisposeMemoryBuffer(buffer);
+            }
+        }
+    }
+}
\ No newline at end of file
@@ -1,71 +1,89 @@
 //! A wrapper around a `LLVMBuilderRef` for a specific context
 
 use super::*;
-use super::types::*;
 use super::c_api::*;
 
+use std::cell::Cell;
+
 /// A wrapper around a `LLVMBuilderRef` for a specific context
-pub struct Builder {
+///
+/// The lifetime of a builder is limited by the blocks it is positioned in, so any values it builds
+/// borrow the module they are inserted into.
+pub struct Builder<'ctx> {
     pub(crate) builder: Option<LLVMBuilderRef>,
+    pub(crate) context: LLVMContextRef,
+    pub(crate) marker: PhantomData<Cell<&'ctx Context>>,
 }
 
-impl Builder {
+impl<'ctx> Builder<'ctx> {
     /// Build a stack allocation
-    pub fn build_alloca(&self, ty: Type) -> Value {
-        Value {
-            value: unsafe {
-                LLVMBuildAlloca(self.builder.unwrap(), ty.ty, into_c("").as_ptr())
-            }
-        }
+    pub fn build_alloca(&self, ty: Type<'ctx>) -> Result<PointerValue<'ctx>, Error> {
+        self.check_ty(ty)?;
+        Ok(PointerValue {
+            base: unsafe {
+                value(LLVMBuildAlloca(self.builder.unwrap(), ty.ty, empty()))
+            },
+        })
     }
 
     /// Build a heap allocation
-    pub fn build_malloc(&self, ty: Type) -> Value {
-        Value {
-            value: unsafe {
-                LLVMBuildMalloc(self.builder.unwrap(), ty.ty, into_c("").as_ptr())
-            }
-        }
+    pub fn build_malloc(&self, ty: Type<'ctx>) -> Result<PointerValue<'ctx>, Error> {
+        self.check_ty(ty)?;
+        Ok(PointerValue {
+            base: unsafe {
+                value(LLVMBuildMalloc(self.builder.unwrap(), ty.ty, empty()))
+            },
+        })
     }
 
     /// Build a stack allocation for an array
-    pub fn build_array_alloca(&self, ty: Type, count: u32) -> Value {
-        Value {
-            value: unsafe {
-                LLVMBuildArrayAlloca(self.builder.unwrap(), ty.ty, ty_i32().const_int(count as u64).value, into_c("").as_ptr())
-            }
-        }
+    pub fn build_array_alloca(&self, ty: Type<'ctx>, count: u32) -> Result<PointerValue<'ctx>, Error> {
+        self.check_ty(ty)?;
+        Ok(PointerValue {
+            base: unsafe {
+                value(LLVMBuildArrayAlloca(self.builder.unwrap(), ty.ty, LLVMConstInt(LLVMInt32TypeInContext(LLVMGetTypeContext(ty.ty)), count as u64, 0), empty()))
+            },
+        })
     }
 
     /// Build a heap allocation for an array
-    pub fn build_array_malloc(&self, ty: Type, count: u32) -> Value {
-        Value {
-            value: unsafe {
-                LLVMBuildArrayMalloc(self.builder.unwrap(), ty.ty, ty_i32().const_int(count as u64).value, into_c("").as_ptr())
-            }
-        }
+    pub fn build_array_malloc(&self, ty: Type<'ctx>, count: u32) -> Result<PointerValue<'ctx>, Error> {
+        self.check_ty(ty)?;
+        Ok(PointerValue {
+            base: unsafe {
+                value(LLVMBuildArrayMalloc(self.builder.unwrap(), ty.ty, LLVMConstInt(LLVMInt32TypeInContext(LLVMGetTypeContext(ty.ty)), count as u64, 0), empty()))
+            },
+        })
     }
 
     /// Build a heap free
-    pub fn build_free(&self, ptr: Value) -> Value {
-        Value {
-            value: unsafe {
-                LLVMBuildFree(self.builder.unwrap(), ptr.value)
-            }
-        }
-    }
-
-    /// Build a call to a function
-    pub fn build_call(&self, func: Value, args: Vec<Value>) -> Value {
-        Value {
-            value: unsafe {
-                LLVMBuildCall(self.builder.unwrap(), func.value, val_vec(&args).as_mut_ptr(), args.len() as u32, into_c("").as_ptr())
+    pub fn build_free(&self, ptr: PointerValue<'ctx>) -> Result<InstructionValue<'ctx>, Error> {
+        self.check(&[ptr.value])?;
+        Ok(InstructionValue {
+            base: unsafe {
+                value(LLVMBuildFree(self.builder.unwrap(), ptr.value))
+            },
+        })
+    }
+
+    /// Build a call to a function or a function pointer
+    ///
+    /// Returns `Error::InvalidType` if `func` doesn't point to a function.
+    pub fn build_call<F>(&self, func: F, args: Vec<Value<'ctx>>) -> Result<Value<'ctx>, Error> where F: Into<PointerValue<'ctx>> {
+        let func = func.into();
+        self.check(&val_vec(&args))?;
+        self.check(&[func.value])?;
+        unsafe {
+            let ty = LLVMGetElementType(LLVMTypeOf(func.value));
+            if LLVMGetTypeKind(ty) != LLVMTypeKind::LLVMFunctionTypeKind {
+                return Err(Error::InvalidType(format!("cannot call a pointer to {}", take_message(LLVMPrintTypeToString(ty)))));
             }
+            Ok(value(LLVMBuildCall(self.builder.unwrap(), func.value, val_vec(&args).as_mut_ptr(), args.len() as u32, empty())))
         }
     }
 
     /// Build a struct initialization for the given type and elements
-    pub fn build_struct_init(&self, ty: Type, elements: Vec<Value>) -> Value {
+    pub fn build_struct_init(&self, ty: Type<'ctx>, elements: Vec<Value<'ctx>>) -> Result<Value<'ctx>, Error> {
         let mut init_elems = Vec::new();
         let mut append_elems = Vec::new();
         for (index, element) in elements.into_iter().enumerate() {
@@ -76,301 +94,378 @@ impl Builder {
                 append_elems.push((index, element));
             }
         }
-        let mut agg = ty.const_struct(init_elems);
+        let mut agg = ty.const_struct(init_elems)?;
         for (index, element) in append_elems.into_iter() {
-            agg = self.build_insert_value(agg, element, index as u32).name(format!("insert_{}", index));
+            agg = self.build_insert_value(agg, element, index as u32)?.name(format!("insert_{}", index))?;
         }
-        agg
+        Ok(agg)
     }
 
     /// Build a struct initialization that stores it into memory with `alloca`
-    pub fn build_struct_alloca_init(&self, ty: Type, elements: Vec<Value>) -> Value {
-        let ptr = self.build_alloca(ty);
+    pub fn build_struct_alloca_init(&self, ty: Type<'ctx>, elements: Vec<Value<'ctx>>) -> Result<PointerValue<'ctx>, Error> {
+        let ptr = self.build_alloca(ty)?;
         for (index, element) in elements.into_iter().enumerate() {
-            let elem_ptr = self.build_struct_gep(ptr, index as u32).name(format!("init_{}", index));
-            self.build_store(element, elem_ptr);
+            let elem_ptr = self.build_struct_gep(ptr, index as u32)?.name(format!("init_{}", index))?;
+            self.build_store(element, elem_ptr)?;
         }
-        ptr
+        Ok(ptr)
     }
 
     /// Build a struct initialization that stores it into memory with `malloc`
-    pub fn build_struct_malloc_init(&self, ty: Type, elements: Vec<Value>) -> Value {
-        let ptr = self.build_malloc(ty);
+    pub fn build_struct_malloc_init(&self, ty: Type<'ctx>, elements: Vec<Value<'ctx>>) -> Result<PointerValue<'ctx>, Error> {
+        let ptr = self.build_malloc(ty)?;
         for (index, element) in elements.into_iter().enumerate() {
-            let elem_ptr = self.build_struct_gep(ptr, index as u32).name(format!("init_{}", index));
-            self.build_store(element, elem_ptr);
+            let elem_ptr = self.build_struct_gep(ptr, index as u32)?.name(format!("init_{}", index))?;
+            self.build_store(element, elem_ptr)?;
         }
-        ptr
+        Ok(ptr)
     }
 
     /// Build an insert value instruction
-    pub fn build_insert_value(&self, agg: Value, elt: Value, index: u32) -> Value {
-        Value {
+    pub fn build_insert_value<V>(&self, agg: Value<'ctx>, elt: V, index: u32) -> Result<Value<'ctx>, Error> where V: Into<Value<'ctx>> {
+        let elt = elt.into();
+        self.check(&[agg.value, elt.value])?;
+        Ok(Value {
             value: unsafe {
-                LLVMBuildInsertValue(self.builder.unwrap(), agg.value, elt.value, index, into_c("").as_ptr())
-            }
-        }
+                LLVMBuildInsertValue(self.builder.unwrap(), agg.value, elt.value, index, empty())
+            },
+            marker: PhantomData,
+        })
     }
 
     /// Build an extract value instruction
-    pub fn build_extract_value(&self, agg: Value, index: u32) -> Value {
-        Value {
+    pub fn build_extract_value(&self, agg: Value<'ctx>, index: u32) -> Result<Value<'ctx>, Error> {
+        self.check(&[agg.value])?;
+        Ok(Value {
             value: unsafe {
-                LLVMBuildExtractValue(self.builder.unwrap(), agg.value, index, into_c("").as_ptr())
-            }
-        }
+                LLVMBuildExtractValue(self.builder.unwrap(), agg.value, index, empty())
+            },
+            marker: PhantomData,
+        })
     }
 
     /// Build a get element pointer instruction
-    pub fn build_gep(&self, ptr: Value, indices: Vec<Value>) -> Value {
-        Value {
-            value: unsafe {
-                LLVMBuildGEP(self.builder.unwrap(), ptr.value,
-                             val_vec(&indices).as_mut_ptr(), indices.len() as u32, into_c("").as_ptr())
-            }
-        }
+    pub fn build_gep(&self, ptr: PointerValue<'ctx>, indices: Vec<IntValue<'ctx>>) -> Result<PointerValue<'ctx>, Error> {
+        self.check(&val_vec(&indices))?;
+        self.check(&[ptr.value])?;
+        Ok(PointerValue {
+            base: unsafe {
+                value(LLVMBuildGEP(self.builder.unwrap(), ptr.value,
+                                   val_vec(&indices).as_mut_ptr(), indices.len() as u32, empty()))
+            },
+        })
     }
 
     /// Build a struct get element pointer instruction
-    pub fn build_struct_gep(&self, ptr: Value, index: u32) -> Value {
-        Value {
-            value: unsafe {
-                LLVMBuildStructGEP(self.builder.unwrap(), ptr.value, index, into_c("").as_ptr())
-            }
-        }
+    pub fn build_struct_gep(&self, ptr: PointerValue<'ctx>, index: u32) -> Result<PointerValue<'ctx>, Error> {
+        self.check(&[ptr.value])?;
+        Ok(PointerValue {
+            base: unsafe {
+                value(LLVMBuildStructGEP(self.builder.unwrap(), ptr.value, index, empty()))
+            },
+        })
     }
 
     /// Build an inbounds get element pointer instruction
-    pub fn build_inbounds_gep(&self, ptr: Value, indices: Vec<Value>) -> Value {
-        Value {
-            value: unsafe {
-                LLVMBuildGEP(self.builder.unwrap(), ptr.value,
-                             val_vec(&indices).as_mut_ptr(), indices.len() as u32, into_c("").as_ptr())
-            }
-        }
+    pub fn build_inbounds_gep(&self, ptr: PointerValue<'ctx>, indices: Vec<IntValue<'ctx>>) -> Result<PointerValue<'ctx>, Error> {
+        self.check(&val_vec(&indices))?;
+        self.check(&[ptr.value])?;
+        Ok(PointerValue {
+            base: unsafe {
+                value(LLVMBuildGEP(self.builder.unwrap(), ptr.value,
+                                   val_vec(&indices).as_mut_ptr(), indices.len() as u32, empty()))
+            },
+        })
     }
 
     /// Build a global string with the given value
-    pub fn build_global_string<S>(&self, string: S) -> Value where S: AsRef<str> {
-        Value {
-            value: unsafe {
-                LLVMBuildGlobalString(self.builder.unwrap(), into_c(string).as_ptr(), into_c("").as_ptr())
-            }
-        }
+    pub fn build_global_string<S>(&self, string: S) -> Result<GlobalValue<'ctx>, Error> where S: AsRef<str> {
+        let string = into_c(string)?;
+        Ok(GlobalValue {
+            base: unsafe {
+                value(LLVMBuildGlobalString(self.builder.unwrap(), string.as_ptr(), empty()))
+            },
+        })
     }
 
     /// Build a global string pointer with the given value
-    pub fn build_global_string_ptr<S>(&self, string: S) -> Value where S: AsRef<str> {
-        Value {
-            value: unsafe {
-                LLVMBuildGlobalStringPtr(self.builder.unwrap(), into_c(string).as_ptr(), into_c("").as_ptr())
-            }
-        }
+    pub fn build_global_string_ptr<S>(&self, string: S) -> Result<PointerValue<'ctx>, Error> where S: AsRef<str> {
+        let string = into_c(string)?;
+        Ok(PointerValue {
+            base: unsafe {
+                value(LLVMBuildGlobalStringPtr(self.builder.unwrap(), string.as_ptr(), empty()))
+            },
+        })
     }
 
     /// Build a store instruction
-    pub fn build_store(&self, val: Value, ptr: Value) {
+    pub fn build_store<V>(&self, val: V, ptr: PointerValue<'ctx>) -> Result<(), Error> where V: Into<Value<'ctx>> {
+        let val = val.into();
+        self.check(&[val.value, ptr.value])?;
         unsafe {
             LLVMBuildStore(self.builder.unwrap(), val.value, ptr.value);
         }
+        Ok(())
     }
 
     /// Build a load instruction
-    pub fn build_load(&self, ptr: Value) -> Value {
-        Value {
+    pub fn build_load(&self, ptr: PointerValue<'ctx>) -> Result<Value<'ctx>, Error> {
+        self.check(&[ptr.value])?;
+        Ok(Value {
             value: unsafe {
-                LLVMBuildLoad(self.builder.unwrap(), ptr.value, into_c("").as_ptr())
-            }
-        }
+                LLVMBuildLoad(self.builder.unwrap(), ptr.value, empty())
+            },
+            marker: PhantomData,
+        })
     }
 
     /// Alloca some memory for an array and then store values in it
-    pub fn build_array_alloca_store(&self, ty: Type, elements: Vec<Value>) -> Value {
-        let mut agg = ty_array(ty, elements.len() as u32).undef();
+    pub fn build_array_alloca_store(&self, ty: Type<'ctx>, elements: Vec<Value<'ctx>>) -> Result<PointerValue<'ctx>, Error> {
+        self.check_ty(ty)?;
+        let mut agg = Type {
+            ty: unsafe { LLVMArrayType(ty.ty, elements.len() as u32) },
+            marker: PhantomData,
+        }.undef();
         for (i, element) in elements.into_iter().enumerate() {
-            agg = self.build_insert_value(agg, element, i as u32).name(format!("insert_elem_{}", i));
+            agg = self.build_insert_value(agg, element, i as u32)?.name(format!("insert_elem_{}", i))?;
         }
-        self.build_pointer_cast(self.build_alloca_store(agg), ty.pointer())
+        self.build_pointer_cast(self.build_alloca_store(agg)?, ty.pointer())
     }
 
     /// Malloc some memory for an array and then store values in it
-    pub fn build_array_malloc_store(&self, ty: Type, elements: Vec<Value>) -> Value {
-        let mut agg = ty_array(ty, elements.len() as u32).undef();
+    pub fn build_array_malloc_store(&self, ty: Type<'ctx>, elements: Vec<Value<'ctx>>) -> Result<PointerValue<'ctx>, Error> {
+        self.check_ty(ty)?;
+        let mut agg = Type {
+            ty: unsafe { LLVMArrayType(ty.ty, elements.len() as u32) },
+            marker: PhantomData,
+        }.undef();
         for (i, element) in elements.into_iter().enumerate() {
-            agg = self.build_insert_value(agg, element, i as u32).name(format!("insert_elem_{}", i));
+            agg = self.build_insert_value(agg, element, i as u32)?.name(format!("insert_elem_{}", i))?;
         }
-        self.build_pointer_cast(self.build_malloc_store(agg), ty.pointer())
+        self.build_pointer_cast(self.build_malloc_store(agg)?, ty.pointer())
     }
 
     /// Alloca some memory and then store a value in it
-    pub fn build_alloca_store(&self, val: Value) -> Value {
-        let ptr = self.build_alloca(val.ty());
-        self.build_store(val, ptr);
-        ptr
+    pub fn build_alloca_store<V>(&self, val: V) -> Result<PointerValue<'ctx>, Error> where V: Into<Value<'ctx>> {
+        let val = val.into();
+        let ptr = self.build_alloca(val.ty())?;
+        self.build_store(val, ptr)?;
+        Ok(ptr)
     }
 
     /// Malloc some memory and then store a value in it
-    pub fn build_malloc_store(&self, val: Value) -> Value {
-        let ptr = self.build_malloc(val.ty());
-        self.build_store(val, ptr);
-        ptr
+    pub fn build_malloc_store<V>(&self, val: V) -> Result<PointerValue<'ctx>, Error> where V: Into<Value<'ctx>> {
+        let val = val.into();
+        let ptr = self.build_malloc(val.ty())?;
+        self.build_store(val, ptr)?;
+        Ok(ptr)
     }
 
     /// Load a value and then free the memory
-    pub fn build_load_free(&self, ptr: Value) -> Value {
-        let val = self.build_load(ptr);
-        self.build_free(ptr);
-        val
+    pub fn build_load_free(&self, ptr: PointerValue<'ctx>) -> Result<Value<'ctx>, Error> {
+        let val = self.build_load(ptr)?;
+        self.build_free(ptr)?;
+        Ok(val)
     }
 
     /// Build a cast from integer to pointer
-    pub fn build_int_to_ptr(&self, val: Value, ptr_ty: Type) -> Value {
-        Value {
-            value: unsafe {
-                LLVMBuildIntToPtr(self.builder.unwrap(), val.value, ptr_ty.ty, into_c("").as_ptr())
-            }
-        }
+    pub fn build_int_to_ptr(&self, val: IntValue<'ctx>, ptr_ty: Type<'ctx>) -> Result<PointerValue<'ctx>, Error> {
+        self.check_ty(ptr_ty)?;
+        self.check(&[val.value])?;
+        Ok(PointerValue {
+            base: unsafe {
+                value(LLVMBuildIntToPtr(self.builder.unwrap(), val.value, ptr_ty.ty, empty()))
+            },
+        })
     }
 
     /// Build a cast from pointer to integer
-    pub fn build_ptr_to_int(&self, ptr: Value, val_ty: Type) -> Value {
-        Value {
-            value: unsafe {
-                LLVMBuildPtrToInt(self.builder.unwrap(), ptr.value, val_ty.ty, into_c("").as_ptr())
-            }
-        }
+    pub fn build_ptr_to_int(&self, ptr: PointerValue<'ctx>, val_ty: Type<'ctx>) -> Result<IntValue<'ctx>, Error> {
+        self.check_ty(val_ty)?;
+        self.check(&[ptr.value])?;
+        Ok(IntValue {
+            base: unsafe {
+                value(LLVMBuildPtrToInt(self.builder.unwrap(), ptr.value, val_ty.ty, empty()))
+            },
+        })
     }
 
     /// Build a pointer cast
-    pub fn build_pointer_cast(&self, ptr: Value, ty: Type) -> Value {
-        Value {
-            value: unsafe {
-                LLVMBuildPointerCast(self.builder.unwrap(), ptr.value, ty.ty, into_c("").as_ptr())
-            }
-        }
+    pub fn build_pointer_cast(&self, ptr: PointerValue<'ctx>, ty: Type<'ctx>) -> Result<PointerValue<'ctx>, Error> {
+        self.check_ty(ty)?;
+        self.check(&[ptr.value])?;
+        Ok(PointerValue {
+            base: unsafe {
+                value(LLVMBuildPointerCast(self.builder.unwrap(), ptr.value, ty.ty, empty()))
+            },
+        })
     }
 
     /// Build an integer cast
-    pub fn build_int_cast(&self, val: Value, ty: Type) -> Value {
-        Value {
-            value: unsafe {
-                LLVMBuildIntCast(self.builder.unwrap(), val.value, ty.ty, into_c("").as_ptr())
-            }
-        }
+    pub fn build_int_cast(&self, val: IntValue<'ctx>, ty: Type<'ctx>) -> Result<IntValue<'ctx>, Error> {
+        self.check_ty(ty)?;
+        self.check(&[val.value])?;
+        Ok(IntValue {
+            base: unsafe {
+                value(LLVMBuildIntCast(self.builder.unwrap(), val.value, ty.ty, empty()))
+            },
+        })
     }
 
     /// Build a bit cast
-    pub fn build_bit_cast(&self, val: Value, ty: Type) -> Value {
-        Value {
+    pub fn build_bit_cast<V>(&self, val: V, ty: Type<'ctx>) -> Result<Value<'ctx>, Error> where V: Into<Value<'ctx>> {
+        let val = val.into();
+        self.check_ty(ty)?;
+        self.check(&[val.value])?;
+        Ok(Value {
             value: unsafe {
-                LLVMBuildBitCast(self.builder.unwrap(), val.value, ty.ty, into_c("").as_ptr())
-            }
-        }
+                LLVMBuildBitCast(self.builder.unwrap(), val.value, ty.ty, empty())
+            },
+            marker: PhantomData,
+        })
     }
 
     /// Build a floating point cast
-    pub fn build_float_cast(&self, val: Value, ty: Type) -> Value {
-        Value {
-            value: unsafe {
-                LLVMBuildFPCast(self.builder.unwrap(), val.value, ty.ty, into_c("").as_ptr())
-            }
-        }
+    pub fn build_float_cast(&self, val: FloatValue<'ctx>, ty: Type<'ctx>) -> Result<FloatValue<'ctx>, Error> {
+        self.check_ty(ty)?;
+        self.check(&[val.value])?;
+        Ok(FloatValue {
+            base: unsafe {
+                value(LLVMBuildFPCast(self.builder.unwrap(), val.value, ty.ty, empty()))
+            },
+        })
     }
 
     /// Build a `ret void` statement
-    pub fn build_ret_void(&self) -> Value {
-        Value {
-            value: unsafe {
-                LLVMBuildRetVoid(self.builder.unwrap())
-            }
-        }
+    pub fn build_ret_void(&self) -> Result<InstructionValue<'ctx>, Error> {
+        Ok(InstructionValue {
+            base: unsafe {
+                value(LLVMBuildRetVoid(self.builder.unwrap()))
+            },
+        })
     }
 
     /// Build a `ret` statement
-    pub fn build_ret(&self, val: Value) -> Value where {
-        Value {
-            value: unsafe {
-                LLVMBuildRet(self.builder.unwrap(), val.value)
-            }
-        }
+    pub fn build_ret<V>(&self, val: V) -> Result<InstructionValue<'ctx>, Error> where V: Into<Value<'ctx>> {
+        let val = val.into();
+        self.check(&[val.value])?;
+        Ok(InstructionValue {
+            base: unsafe {
+                value(LLVMBuildRet(self.builder.unwrap(), val.value))
+            },
+        })
     }
 
     /// Build an unreachable instruction
-    pub fn build_unreachable(&self) {
+    pub fn build_unreachable(&self) -> Result<(), Error> {
         unsafe {
             LLVMBuildUnreachable(self.builder.unwrap());
         }
+        Ok(())
     }
 
     /// Build a branch instruction to the given block
-    pub fn build_br(&self, block: BasicBlock) {
+    pub fn build_br(&self, block: BasicBlock<'ctx>) -> Result<(), Error> {
+        self.check_block(block)?;
         unsafe {
             LLVMBuildBr(self.builder.unwrap(), block.basic_block);
         }
+        Ok(())
     }
 
     /// Build an if statement that branches to the given blocks
-    pub fn build_if(&self, condition: Value, then_block: BasicBlock, else_block: BasicBlock) {
+    pub fn build_if(&self, condition: IntValue<'ctx>, then_block: BasicBlock<'ctx>, else_block: BasicBlock<'ctx>) -> Result<(), Error> {
+        self.check_block(then_block)?;
+        self.check_block(else_block)?;
+        self.check(&[condition.value])?;
         unsafe {
             LLVMBuildCondBr(self.builder.unwrap(), condition.value, then_block.basic_block, else_block.basic_block);
         }
+        Ok(())
     }
 
     /// Build a switch statement that branches to the given blocks
-    pub fn build_switch(&self, val: Value, cases: Vec<(Value, BasicBlock)>, default: BasicBlock) {
+    pub fn build_switch(&self, val: IntValue<'ctx>, cases: Vec<(IntValue<'ctx>, BasicBlock<'ctx>)>, default: BasicBlock<'ctx>) -> Result<(), Error> {
+        self.check_block(default)?;
+        self.check(&[val.value])?;
+        for &(val, bb) in &cases {
+            self.check_block(bb)?;
+            self.check(&[val.value])?;
+        }
         unsafe {
             let switch = LLVMBuildSwitch(self.builder.unwrap(), val.value, default.basic_block, cases.len() as u32);
             for (val, bb) in cases {
                 LLVMAddCase(switch, val.value, bb.basic_block);
             }
         }
+        Ok(())
     }
 
     /// Build a phi instruction that takes ceratin values from certain blocks
-    pub fn build_phi(&self, incoming: Vec<(Value, BasicBlock)>) -> Value {
-        Value {
-            value: unsafe {
-                if incoming.is_empty() {
-                    panic!("phi node must have an incoming block list");
-                } else {
-                    let phi = LLVMBuildPhi(
-                        self.builder.unwrap(),
-                        incoming[0].0.ty().ty,
-                        into_c("").as_ptr()
-                    );
-                    let len = incoming.len();
-                    let mut values = Vec::new();
-                    let mut blocks = Vec::new();
-                    for (val, block) in incoming {
-                        values.push(val.value);
-                        blocks.push(block.basic_block);
-                    }
-                    LLVMAddIncoming(phi, values.as_mut_ptr(), blocks.as_mut_ptr(), len as u32);
-                    phi
-                }
-            }
-        }
+    ///
+    /// The type of the phi instruction is taken from the first value, so this returns
+    /// `Error::InvalidArgument` if there are no incoming values.
+    pub fn build_phi(&self, incoming: Vec<(Value<'ctx>, BasicBlock<'ctx>)>) -> Result<PhiValue<'ctx>, Error> {
+        if incoming.is_empty() {
+            return Err(Error::InvalidArgument("phi node must have an incoming block list".to_owned()));
+        }
+        self.check(&[incoming[0].0.value])?;
+        let phi = PhiValue {
+            base: unsafe {
+                value(LLVMBuildPhi(self.builder.unwrap(), incoming[0].0.ty().ty, empty()))
+            },
+        };
+        phi.add_incoming(incoming)
     }
 
     /// Position the builder at a given value in a basic block
-    pub fn position_in_block(&self, bb: BasicBlock, val: Value) {
+    pub fn position_in_block(&self, bb: BasicBlock<'ctx>, val: InstructionValue<'ctx>) -> Result<(), Error> {
+        self.check_block(bb)?;
+        self.check(&[val.value])?;
         unsafe {
             LLVMPositionBuilder(self.builder.unwrap(), bb.basic_block, val.value);
         }
+        Ok(())
     }
 
     /// Position the builder at the end of the basic block
-    pub fn position_at_end(&self, bb: BasicBlock) {
+    pub fn position_at_end(&self, bb: BasicBlock<'ctx>) -> Result<(), Error> {
+        self.check_block(bb)?;
         unsafe {
             LLVMPositionBuilderAtEnd(self.builder.unwrap(), bb.basic_block);
         }
+        Ok(())
     }
 
     /// Position the builder before a value
-    pub fn position_before(&self, val: Value) {
+    pub fn position_before(&self, val: InstructionValue<'ctx>) -> Result<(), Error> {
+        self.check(&[val.value])?;
         unsafe {
             LLVMPositionBuilderBefore(self.builder.unwrap(), val.value);
         }
+        Ok(())
+    }
+
+    /// Returns an error if any of the values belong to another context
+    fn check(&self, values: &[LLVMValueRef]) -> Result<(), Error> {
+        for &value in values {
+            unsafe {
+                check_context(self.context, LLVMTypeOf(value))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns an error if a type belongs to another context
+    fn check_ty(&self, ty: Type<'ctx>) -> Result<(), Error> {
+        unsafe {
+            check_context(self.context, ty.ty)
+        }
+    }
+
+    /// Returns an error if a basic block belongs to another context
+    fn check_block(&self, bb: BasicBlock<'ctx>) -> Result<(), Error> {
+        self.check(&[unsafe { LLVMBasicBlockAsValue(bb.basic_block) }])
     }
 
     /// Returns the internal builder reference
@@ -384,403 +479,447 @@ impl Builder {
     }
 
     /// Builds a null check
-    pub fn build_is_null(&self, val: Value) -> Value {
-        Value {
-            value: unsafe {
-                LLVMBuildIsNull(self.builder.unwrap(), val.value, into_c("").as_ptr())
-            }
-        }
+    pub fn build_is_null(&self, val: PointerValue<'ctx>) -> Result<IntValue<'ctx>, Error> {
+        self.check(&[val.value])?;
+        Ok(IntValue {
+            base: unsafe {
+                value(LLVMBuildIsNull(self.builder.unwrap(), val.value, empty()))
+            },
+        })
     }
 
     /// Builds a null check
-    pub fn build_is_not_null(&self, val: Value) -> Value {
-        Value {
-            value: unsafe {
-                LLVMBuildIsNotNull(self.builder.unwrap(), val.value, into_c("").as_ptr())
-            }
-        }
+    pub fn build_is_not_null(&self, val: PointerValue<'ctx>) -> Result<IntValue<'ctx>, Error> {
+        self.check(&[val.value])?;
+        Ok(IntValue {
+            base: unsafe {
+                value(LLVMBuildIsNotNull(self.builder.unwrap(), val.value, empty()))
+            },
+        })
     }
 
     /// Builds an integer `add` instruction
-    pub fn build_int_add(&self, a: Value, b: Value) -> Value {
-        Value {
-            value: unsafe {
-                LLVMBuildAdd(self.builder.unwrap(), a.value, b.value, into_c("").as_ptr())
-            }
-        }
+    pub fn build_int_add(&self, a: IntValue<'ctx>, b: IntValue<'ctx>) -> Result<IntValue<'ctx>, Error> {
+        self.check(&[a.value, b.value])?;
+        Ok(IntValue {
+            base: unsafe {
+                value(LLVMBuildAdd(self.builder.unwrap(), a.value, b.value, empty()))
+            },
+        })
     }
 
     /// Builds an integer `sub` instruction
-    pub fn build_int_sub(&self, a: Value, b: Value) -> Value {
-        Value {
-            value: unsafe {
-                LLVMBuildSub(self.builder.unwrap(), a.value, b.value, into_c("").as_ptr())
-            }
-        }
+    pub fn build_int_sub(&self, a: IntValue<'ctx>, b: IntValue<'ctx>) -> Result<IntValue<'ctx>, Error> {
+        self.check(&[a.value, b.value])?;
+        Ok(IntValue {
+            base: unsafe {
+                value(LLVMBuildSub(self.builder.unwrap(), a.value, b.value, empty()))
+            },
+        })
     }
 
     /// Builds an integer `mul` instruction
-    pub fn build_int_mul(&self, a: Value, b: Value) -> Value {
-        Value {
-            value: unsafe {
-                LLVMBuildMul(self.builder.unwrap(), a.value, b.value, into_c("").as_ptr())
-            }
-        }
+    pub fn build_int_mul(&self, a: IntValue<'ctx>, b: IntValue<'ctx>) -> Result<IntValue<'ctx>, Error> {
+        self.check(&[a.value, b.value])?;
+        Ok(IntValue {
+            base: unsafe {
+                value(LLVMBuildMul(self.builder.unwrap(), a.value, b.value, empty()))
+            },
+        })
     }
 
     /// Builds an integer `udiv` instruction
-    pub fn build_int_udiv(&self, a: Value, b: Value) -> Value {
-        Value {
-            value: unsafe {
-                LLVMBuildUDiv(self.builder.unwrap(), a.value, b.value, into_c("").as_ptr())
-            }
-        }
+    pub fn build_int_udiv(&self, a: IntValue<'ctx>, b: IntValue<'ctx>) -> Result<IntValue<'ctx>, Error> {
+        self.check(&[a.value, b.value])?;
+        Ok(IntValue {
+            base: unsafe {
+                value(LLVMBuildUDiv(self.builder.unwrap(), a.value, b.value, empty()))
+            },
+        })
     }
 
     /// Builds an integer `sdiv` instruction
-    pub fn build_int_sdiv(&self, a: Value, b: Value) -> Value {
-        Value {
-            value: unsafe {
-                LLVMBuildSDiv(self.builder.unwrap(), a.value, b.value, into_c("").as_ptr())
-            }
-        }
+    pub fn build_int_sdiv(&self, a: IntValue<'ctx>, b: IntValue<'ctx>) -> Result<IntValue<'ctx>, Error> {
+        self.check(&[a.value, b.value])?;
+        Ok(IntValue {
+            base: unsafe {
+                value(LLVMBuildSDiv(self.builder.unwrap(), a.value, b.value, empty()))
+            },
+        })
     }
 
     /// Builds an integer `urem` instruction
-    pub fn build_int_urem(&self, a: Value, b: Value) -> Value {
-        Value {
-            value: unsafe {
-                LLVMBuildSRem(self.builder.unwrap(), a.value, b.value, into_c("").as_ptr())
-            }
-        }
+    pub fn build_int_urem(&self, a: IntValue<'ctx>, b: IntValue<'ctx>) -> Result<IntValue<'ctx>, Error> {
+        self.check(&[a.value, b.value])?;
+        Ok(IntValue {
+            base: unsafe {
+                value(LLVMBuildSRem(self.builder.unwrap(), a.value, b.value, empty()))
+            },
+        })
     }
 
     /// Builds an integer `srem` instruction
-    pub fn build_int_srem(&self, a: Value, b: Value) -> Value {
-        Value {
-            value: unsafe {
-                LLVMBuildSRem(self.builder.unwrap(), a.value, b.value, into_c("").as_ptr())
-            }
-        }
+    pub fn build_int_srem(&self, a: IntValue<'ctx>, b: IntValue<'ctx>) -> Result<IntValue<'ctx>, Error> {
+        self.check(&[a.value, b.value])?;
+        Ok(IntValue {
+            base: unsafe {
+                value(LLVMBuildSRem(self.builder.unwrap(), a.value, b.value, empty()))
+            },
+        })
     }
 
     /// Builds a `shl` instruction
-    pub fn build_shl(&self, a: Value, b: Value) -> Value {
-        Value {
-            value: unsafe {
-                LLVMBuildShl(self.builder.unwrap(), a.value, b.value, into_c("").as_ptr())
-            }
-        }
+    pub fn build_shl(&self, a: IntValue<'ctx>, b: IntValue<'ctx>) -> Result<IntValue<'ctx>, Error> {
+        self.check(&[a.value, b.value])?;
+        Ok(IntValue {
+            base: unsafe {
+                value(LLVMBuildShl(self.builder.unwrap(), a.value, b.value, empty()))
+            },
+        })
     }
 
     /// Builds a `lshr` instruction
-    pub fn build_lshr(&self, a: Value, b: Value) -> Value {
-        Value {
-            value: unsafe {
-                LLVMBuildLShr(self.builder.unwrap(), a.value, b.value, into_c("").as_ptr())
-            }
-        }
+    pub fn build_lshr(&self, a: IntValue<'ctx>, b: IntValue<'ctx>) -> Result<IntValue<'ctx>, Error> {
+        self.check(&[a.value, b.value])?;
+        Ok(IntValue {
+            base: unsafe {
+                value(LLVMBuildLShr(self.builder.unwrap(), a.value, b.value, empty()))
+            },
+        })
     }
 
     /// Builds an `ashr` instruction
-    pub fn build_ashr(&self, a: Value, b: Value) -> Value {
-        Value {
-            value: unsafe {
-                LLVMBuildAShr(self.builder.unwrap(), a.value, b.value, into_c("").as_ptr())
-            }
-        }
+    pub fn build_ashr(&self, a: IntValue<'ctx>, b: IntValue<'ctx>) -> Result<IntValue<'ctx>, Error> {
+        self.check(&[a.value, b.value])?;
+        Ok(IntValue {
+            base: unsafe {
+                value(LLVMBuildAShr(self.builder.unwrap(), a.value, b.value, empty()))
+            },
+        })
     }
 
     /// Builds an `and` instruction
-    pub fn build_and(&self, a: Value, b: Value) -> Value {
-        Value {
-            value: unsafe {
-                LLVMBuildAnd(self.builder.unwrap(), a.value, b.value, into_c("").as_ptr())
-            }
-        }
+    pub fn build_and(&self, a: IntValue<'ctx>, b: IntValue<'ctx>) -> Result<IntValue<'ctx>, Error> {
+        self.check(&[a.value, b.value])?;
+        Ok(IntValue {
+            base: unsafe {
+                value(LLVMBuildAnd(self.builder.unwrap(), a.value, b.value, empty()))
+            },
+        })
     }
 
     /// Builds an `or` instruction
-    pub fn build_or(&self, a: Value, b: Value) -> Value {
-        Value {
-            value: unsafe {
-                LLVMBuildOr(self.builder.unwrap(), a.value, b.value, into_c("").as_ptr())
-            }
-        }
+    pub fn build_or(&self, a: IntValue<'ctx>, b: IntValue<'ctx>) -> Result<IntValue<'ctx>, Error> {
+        self.check(&[a.value, b.value])?;
+        Ok(IntValue {
+            base: unsafe {
+                value(LLVMBuildOr(self.builder.unwrap(), a.value, b.value, empty()))
+            },
+        })
     }
 
     /// Builds an `xor` instruction
-    pub fn build_xor(&self, a: Value, b: Value) -> Value {
-        Value {
-            value: unsafe {
-                LLVMBuildXor(self.builder.unwrap(), a.value, b.value, into_c("").as_ptr())
-            }
-        }
+    pub fn build_xor(&self, a: IntValue<'ctx>, b: IntValue<'ctx>) -> Result<IntValue<'ctx>, Error> {
+        self.check(&[a.value, b.value])?;
+        Ok(IntValue {
+            base: unsafe {
+                value(LLVMBuildXor(self.builder.unwrap(), a.value, b.value, empty()))
+            },
+        })
     }
 
     /// Builds an integer `eq` check
-    pub fn build_int_eq(&self, a: Value, b: Value) -> Value {
-        Value {
-            value: unsafe {
-                LLVMBuildICmp(self.builder.unwrap(), LLVMIntPredicate::LLVMIntEQ, a.value, b.value, into_c("").as_ptr())
-            }
-        }
+    pub fn build_int_eq(&self, a: IntValue<'ctx>, b: IntValue<'ctx>) -> Result<IntValue<'ctx>, Error> {
+        self.check(&[a.value, b.value])?;
+        Ok(IntValue {
+            base: unsafe {
+                value(LLVMBuildICmp(self.builder.unwrap(), LLVMIntPredicate::LLVMIntEQ, a.value, b.value, empty()))
+            },
+        })
     }
 
     /// Builds an integer `ne` check
-    pub fn build_int_ne(&self, a: Value, b: Value) -> Value {
-        Value {
-            value: unsafe {
-                LLVMBuildICmp(self.builder.unwrap(), LLVMIntPredicate::LLVMIntNE, a.value, b.value, into_c("").as_ptr())
-            }
-        }
+    pub fn build_int_ne(&self, a: IntValue<'ctx>, b: IntValue<'ctx>) -> Result<IntValue<'ctx>, Error> {
+        self.check(&[a.value, b.value])?;
+        Ok(IntValue {
+            base: unsafe {
+                value(LLVMBuildICmp(self.builder.unwrap(), LLVMIntPredicate::LLVMIntNE, a.value, b.value, empty()))
+            },
+        })
     }
 
     /// Builds an integer `ule` check
-    pub fn build_int_ule(&self, a: Value, b: Value) -> Value {
-        Value {
-            value: unsafe {
-                LLVMBuildICmp(self.builder.unwrap(), LLVMIntPredicate::LLVMIntULE, a.value, b.value, into_c("").as_ptr())
-            }
-        }
+    pub fn build_int_ule(&self, a: IntValue<'ctx>, b: IntValue<'ctx>) -> Result<IntValue<'ctx>, Error> {
+        self.check(&[a.value, b.value])?;
+        Ok(IntValue {
+            base: unsafe {
+                value(LLVMBuildICmp(self.builder.unwrap(), LLVMIntPredicate::LLVMIntULE, a.value, b.value, empty()))
+            },
+        })
     }
 
     /// Builds an integer `ult` check
-    pub fn build_int_ult(&self, a: Value, b: Value) -> Value {
-        Value {
-            value: unsafe {
-                LLVMBuildICmp(self.builder.unwrap(), LLVMIntPredicate::LLVMIntULT, a.value, b.value, into_c("").as_ptr())
-            }
-        }
+    pub fn build_int_ult(&self, a: IntValue<'ctx>, b: IntValue<'ctx>) -> Result<IntValue<'ctx>, Error> {
+        self.check(&[a.value, b.value])?;
+        Ok(IntValue {
+            base: unsafe {
+                value(LLVMBuildICmp(self.builder.unwrap(), LLVMIntPredicate::LLVMIntULT, a.value, b.value, empty()))
+            },
+        })
     }
 
     /// Builds an integer `uge` check
-    pub fn build_int_uge(&self, a: Value, b: Value) -> Value {
-        Value {
-            value: unsafe {
-                LLVMBuildICmp(self.builder.unwrap(), LLVMIntPredicate::LLVMIntUGE, a.value, b.value, into_c("").as_ptr())
-            }
-        }
+    pub fn build_int_uge(&self, a: IntValue<'ctx>, b: IntValue<'ctx>) -> Result<IntValue<'ctx>, Error> {
+        self.check(&[a.value, b.value])?;
+        Ok(IntValue {
+            base: unsafe {
+                value(LLVMBuildICmp(self.builder.unwrap(), LLVMIntPredicate::LLVMIntUGE, a.value, b.value, empty()))
+            },
+        })
     }
 
     /// Builds an integer `ugt` check
-    pub fn build_int_ugt(&self, a: Value, b: Value) -> Value {
-        Value {
-            value: unsafe {
-                LLVMBuildICmp(self.builder.unwrap(), LLVMIntPredicate::LLVMIntUGT, a.value, b.value, into_c("").as_ptr())
-            }
-        }
+    pub fn build_int_ugt(&self, a: IntValue<'ctx>, b: IntValue<'ctx>) -> Result<IntValue<'ctx>, Error> {
+        self.check(&[a.value, b.value])?;
+        Ok(IntValue {
+            base: unsafe {
+                value(LLVMBuildICmp(self.builder.unwrap(), LLVMIntPredicate::LLVMIntUGT, a.value, b.value, empty()))
+            },
+        })
     }
 
     /// Builds an integer `sle` check
-    pub fn build_int_sle(&self, a: Value, b: Value) -> Value {
-        Value {
-            value: unsafe {
-                LLVMBuildICmp(self.builder.unwrap(), LLVMIntPredicate::LLVMIntSLE, a.value, b.value, into_c("").as_ptr())
-            }
-        }
+    pub fn build_int_sle(&self, a: IntValue<'ctx>, b: IntValue<'ctx>) -> Result<IntValue<'ctx>, Error> {
+        self.check(&[a.value, b.value])?;
+        Ok(IntValue {
+            base: unsafe {
+                value(LLVMBuildICmp(self.builder.unwrap(), LLVMIntPredicate::LLVMIntSLE, a.value, b.value, empty()))
+            },
+        })
     }
 
     /// Builds an integer `slt` check
-    pub fn build_int_slt(&self, a: Value, b: Value) -> Value {
-        Value {
-            value: unsafe {
-                LLVMBuildICmp(self.builder.unwrap(), LLVMIntPredicate::LLVMIntSLT, a.value, b.value, into_c("").as_ptr())
-            }
-        }
+    pub fn build_int_slt(&self, a: IntValue<'ctx>, b: IntValue<'ctx>) -> Result<IntValue<'ctx>, Error> {
+        self.check(&[a.value, b.value])?;
+        Ok(IntValue {
+            base: unsafe {
+                value(LLVMBuildICmp(self.builder.unwrap(), LLVMIntPredicate::LLVMIntSLT, a.value, b.value, empty()))
+            },
+        })
     }
 
     /// Builds an integer `sge` check
-    pub fn build_int_sge(&self, a: Value, b: Value) -> Value {
-        Value {
-            value: unsafe {
-                LLVMBuildICmp(self.builder.unwrap(), LLVMIntPredicate::LLVMIntSGE, a.value, b.value, into_c("").as_ptr())
-            }
-        }
+    pub fn build_int_sge(&self, a: IntValue<'ctx>, b: IntValue<'ctx>) -> Result<IntValue<'ctx>, Error> {
+        self.check(&[a.value, b.value])?;
+        Ok(IntValue {
+            base: unsafe {
+                value(LLVMBuildICmp(self.builder.unwrap(), LLVMIntPredicate::LLVMIntSGE, a.value, b.value, empty()))
+            },
+        })
     }
 
     /// Builds an integer `sgt` check
-    pub fn build_int_sgt(&self, a: Value, b: Value) -> Value {
-        Value {
-            value: unsafe {
-                LLVMBuildICmp(self.builder.unwrap(), LLVMIntPredicate::LLVMIntSGT, a.value, b.value, into_c("").as_ptr())
-            }
-        }
+    pub fn build_int_sgt(&self, a: IntValue<'ctx>, b: IntValue<'ctx>) -> Result<IntValue<'ctx>, Error> {
+        self.check(&[a.value, b.value])?;
+        Ok(IntValue {
+            base: unsafe {
+                value(LLVMBuildICmp(self.builder.unwrap(), LLVMIntPredicate::LLVMIntSGT, a.value, b.value, empty()))
+            },
+        })
     }
 
     /// Builds a float `add` instruction
-    pub fn build_float_add(&self, a: Value, b: Value) -> Value {
-        Value {
-            value: unsafe {
-                LLVMBuildFAdd(self.builder.unwrap(), a.value, b.value, into_c("").as_ptr())
-            }
-        }
+    pub fn build_float_add(&self, a: FloatValue<'ctx>, b: FloatValue<'ctx>) -> Result<FloatValue<'ctx>, Error> {
+        self.check(&[a.value, b.value])?;
+        Ok(FloatValue {
+            base: unsafe {
+                value(LLVMBuildFAdd(self.builder.unwrap(), a.value, b.value, empty()))
+            },
+        })
     }
 
     /// Builds a float `sub` instruction
-    pub fn build_float_sub(&self, a: Value, b: Value) -> Value {
-        Value {
-            value: unsafe {
-                LLVMBuildFSub(self.builder.unwrap(), a.value, b.value, into_c("").as_ptr())
-            }
-        }
+    pub fn build_float_sub(&self, a: FloatValue<'ctx>, b: FloatValue<'ctx>) -> Result<FloatValue<'ctx>, Error> {
+        self.check(&[a.value, b.value])?;
+        Ok(FloatValue {
+            base: unsafe {
+                value(LLVMBuildFSub(self.builder.unwrap(), a.value, b.value, empty()))
+            },
+        })
     }
 
     /// Builds a float `mul` instruction
-    pub fn build_float_mul(&self, a: Value, b: Value) -> Value {
-        Value {
-            value: unsafe {
-                LLVMBuildFMul(self.builder.unwrap(), a.value, b.value, into_c("").as_ptr())
-            }
-        }
+    pub fn build_float_mul(&self, a: FloatValue<'ctx>, b: FloatValue<'ctx>) -> Result<FloatValue<'ctx>, Error> {
+        self.check(&[a.value, b.value])?;
+        Ok(FloatValue {
+            base: unsafe {
+                value(LLVMBuildFMul(self.builder.unwrap(), a.value, b.value, empty()))
+            },
+        })
     }
 
     /// Builds a float `div` instruction
-    pub fn build_float_div(&self, a: Value, b: Value) -> Value {
-        Value {
-            value: unsafe {
-                LLVMBuildFDiv(self.builder.unwrap(), a.value, b.value, into_c("").as_ptr())
-            }
-        }
+    pub fn build_float_div(&self, a: FloatValue<'ctx>, b: FloatValue<'ctx>) -> Result<FloatValue<'ctx>, Error> {
+        self.check(&[a.value, b.value])?;
+        Ok(FloatValue {
+            base: unsafe {
+                value(LLVMBuildFDiv(self.builder.unwrap(), a.value, b.value, empty()))
+            },
+        })
     }
 
     /// Builds a float `rem` instruction
-    pub fn build_float_rem(&self, a: Value, b: Value) -> Value {
-        Value {
-            value: unsafe {
-                LLVMBuildFRem(self.builder.unwrap(), a.value, b.value, into_c("").as_ptr())
-            }
-        }
+    pub fn build_float_rem(&self, a: FloatValue<'ctx>, b: FloatValue<'ctx>) -> Result<FloatValue<'ctx>, Error> {
+        self.check(&[a.value, b.value])?;
+        Ok(FloatValue {
+            base: unsafe {
+                value(LLVMBuildFRem(self.builder.unwrap(), a.value, b.value, empty()))
+            },
+        })
     }
 
     /// Builds a float `eq` check
-    pub fn build_float_eq(&self, a: Value, b: Value) -> Value {
-        Value {
-            value: unsafe {
-                LLVMBuildFCmp(self.builder.unwrap(), LLVMRealPredicate::LLVMRealUEQ, a.value, b.value, into_c("").as_ptr())
-            }
-        }
+    pub fn build_float_eq(&self, a: FloatValue<'ctx>, b: FloatValue<'ctx>) -> Result<IntValue<'ctx>, Error> {
+        self.check(&[a.value, b.value])?;
+        Ok(IntValue {
+            base: unsafe {
+                value(LLVMBuildFCmp(self.builder.unwrap(), LLVMRealPredicate::LLVMRealUEQ, a.value, b.value, empty()))
+            },
+        })
     }
 
     /// Builds a float `ne` check
-    pub fn build_float_ne(&self, a: Value, b: Value) -> Value {
-        Value {
-            value: unsafe {
-                LLVMBuildFCmp(self.builder.unwrap(), LLVMRealPredicate::LLVMRealUNE, a.value, b.value, into_c("").as_ptr())
-            }
-        }
+    pub fn build_float_ne(&self, a: FloatValue<'ctx>, b: FloatValue<'ctx>) -> Result<IntValue<'ctx>, Error> {
+        self.check(&[a.value, b.value])?;
+        Ok(IntValue {
+            base: unsafe {
+                value(LLVMBuildFCmp(self.builder.unwrap(), LLVMRealPredicate::LLVMRealUNE, a.value, b.value, empty()))
+            },
+        })
     }
 
     /// Builds a float `le` check
-    pub fn build_float_le(&self, a: Value, b: Value) -> Value {
-        Value {
-            value: unsafe {
-                LLVMBuildFCmp(self.builder.unwrap(), LLVMRealPredicate::LLVMRealULE, a.value, b.value, into_c("").as_ptr())
-            }
-        }
+    pub fn build_float_le(&self, a: FloatValue<'ctx>, b: FloatValue<'ctx>) -> Result<IntValue<'ctx>, Error> {
+        self.check(&[a.value, b.value])?;
+        Ok(IntValue {
+            base: unsafe {
+                value(LLVMBuildFCmp(self.builder.unwrap(), LLVMRealPredicate::LLVMRealULE, a.value, b.value, empty()))
+            },
+        })
     }
 
     /// Builds a float `lt` check
-    pub fn build_float_lt(&self, a: Value, b: Value) -> Value {
-        Value {
-            value: unsafe {
-                LLVMBuildFCmp(self.builder.unwrap(), LLVMRealPredicate::LLVMRealULT, a.value, b.value, into_c("").as_ptr())
-            }
-        }
+    pub fn build_float_lt(&self, a: FloatValue<'ctx>, b: FloatValue<'ctx>) -> Result<IntValue<'ctx>, Error> {
+        self.check(&[a.value, b.value])?;
+        Ok(IntValue {
+            base: unsafe {
+                value(LLVMBuildFCmp(self.builder.unwrap(), LLVMRealPredicate::LLVMRealULT, a.value, b.value, empty()))
+            },
+        })
     }
 
     /// Builds a float `ge` check
-    pub fn build_float_ge(&self, a: Value, b: Value) -> Value {
-        Value {
-            value: unsafe {
-                LLVMBuildFCmp(self.builder.unwrap(), LLVMRealPredicate::LLVMRealUGE, a.value, b.value, into_c("").as_ptr())
-            }
-        }
+    pub fn build_float_ge(&self, a: FloatValue<'ctx>, b: FloatValue<'ctx>) -> Result<IntValue<'ctx>, Error> {
+        self.check(&[a.value, b.value])?;
+        Ok(IntValue {
+            base: unsafe {
+                value(LLVMBuildFCmp(self.builder.unwrap(), LLVMRealPredicate::LLVMRealUGE, a.value, b.value, empty()))
+            },
+        })
     }
 
     /// Builds a float `gt` check
-    pub fn build_float_gt(&self, a: Value, b: Value) -> Value {
-        Value {
-            value: unsafe {
-                LLVMBuildFCmp(self.builder.unwrap(), LLVMRealPredicate::LLVMRealUGT, a.value, b.value, into_c("").as_ptr())
-            }
-        }
+    pub fn build_float_gt(&self, a: FloatValue<'ctx>, b: FloatValue<'ctx>) -> Result<IntValue<'ctx>, Error> {
+        self.check(&[a.value, b.value])?;
+        Ok(IntValue {
+            base: unsafe {
+                value(LLVMBuildFCmp(self.builder.unwrap(), LLVMRealPredicate::LLVMRealUGT, a.value, b.value, empty()))
+            },
+        })
     }
 
     /// Builds an ordered float `eq` check
-    pub fn build_float_ord_eq(&self, a: Value, b: Value) -> Value {
-        Value {
-            value: unsafe {
-                LLVMBuildFCmp(self.builder.unwrap(), LLVMRealPredicate::LLVMRealOEQ, a.value, b.value, into_c("").as_ptr())
-            }
-        }
+    pub fn build_float_ord_eq(&self, a: FloatValue<'ctx>, b: FloatValue<'ctx>) -> Result<IntValue<'ctx>, Error> {
+        self.check(&[a.value, b.value])?;
+        Ok(IntValue {
+            base: unsafe {
+                value(LLVMBuildFCmp(self.builder.unwrap(), LLVMRealPredicate::LLVMRealOEQ, a.value, b.value, empty()))
+            },
+        })
     }
 
     /// Builds an ordered float `ne` check
-    pub fn build_float_ord_ne(&self, a: Value, b: Value) -> Value {
-        Value {
-            value: unsafe {
-                LLVMBuildFCmp(self.builder.unwrap(), LLVMRealPredicate::LLVMRealONE, a.value, b.value, into_c("").as_ptr())
-            }
-        }
+    pub fn build_float_ord_ne(&self, a: FloatValue<'ctx>, b: FloatValue<'ctx>) -> Result<IntValue<'ctx>, Error> {
+        self.check(&[a.value, b.value])?;
+        Ok(IntValue {
+            base: unsafe {
+                value(LLVMBuildFCmp(self.builder.unwrap(), LLVMRealPredicate::LLVMRealONE, a.value, b.value, empty()))
+            },
+        })
     }
 
     /// Builds an ordered float `le` check
-    pub fn build_float_ord_le(&self, a: Value, b: Value) -> Value {
-        Value {
-            value: unsafe {
-                LLVMBuildFCmp(self.builder.unwrap(), LLVMRealPredicate::LLVMRealOLE, a.value, b.value, into_c("").as_ptr())
-            }
-        }
+    pub fn build_float_ord_le(&self, a: FloatValue<'ctx>, b: FloatValue<'ctx>) -> Result<IntValue<'ctx>, Error> {
+        self.check(&[a.value, b.value])?;
+        Ok(IntValue {
+            base: unsafe {
+                value(LLVMBuildFCmp(self.builder.unwrap(), LLVMRealPredicate::LLVMRealOLE, a.value, b.value, empty()))
+            },
+        })
     }
 
     /// Builds an ordered float `lt` check
-    pub fn build_float_ord_lt(&self, a: Value, b: Value) -> Value {
-        Value {
-            value: unsafe {
-                LLVMBuildFCmp(self.builder.unwrap(), LLVMRealPredicate::LLVMRealOLT, a.value, b.value, into_c("").as_ptr())
-            }
-        }
+    pub fn build_float_ord_lt(&self, a: FloatValue<'ctx>, b: FloatValue<'ctx>) -> Result<IntValue<'ctx>, Error> {
+        self.check(&[a.value, b.value])?;
+        Ok(IntValue {
+            base: unsafe {
+                value(LLVMBuildFCmp(self.builder.unwrap(), LLVMRealPredicate::LLVMRealOLT, a.value, b.value, empty()))
+            },
+        })
     }
 
     /// Builds an ordered float `ge` check
-    pub fn build_float_ord_ge(&self, a: Value, b: Value) -> Value {
-        Value {
-            value: unsafe {
-                LLVMBuildFCmp(self.builder.unwrap(), LLVMRealPredicate::LLVMRealOGE, a.value, b.value, into_c("").as_ptr())
-            }
-        }
+    pub fn build_float_ord_ge(&self, a: FloatValue<'ctx>, b: FloatValue<'ctx>) -> Result<IntValue<'ctx>, Error> {
+        self.check(&[a.value, b.value])?;
+        Ok(IntValue {
+            base: unsafe {
+                value(LLVMBuildFCmp(self.builder.unwrap(), LLVMRealPredicate::LLVMRealOGE, a.value, b.value, empty()))
+            },
+        })
     }
 
     /// Builds an ordered float `gt` check
-    pub fn build_float_ord_gt(&self, a: Value, b: Value) -> Value {
-        Value {
-            value: unsafe {
-                LLVMBuildFCmp(self.builder.unwrap(), LLVMRealPredicate::LLVMRealOGT, a.value, b.value, into_c("").as_ptr())
-            }
-        }
+    pub fn build_float_ord_gt(&self, a: FloatValue<'ctx>, b: FloatValue<'ctx>) -> Result<IntValue<'ctx>, Error> {
+        self.check(&[a.value, b.value])?;
+        Ok(IntValue {
+            base: unsafe {
+                value(LLVMBuildFCmp(self.builder.unwrap(), LLVMRealPredicate::LLVMRealOGT, a.value, b.value, empty()))
+            },
+        })
     }
 
     /// Builds a check for an ordered float
-    pub fn build_float_is_ord(&self, a: Value, b: Value) -> Value {
-        Value {
-            value: unsafe {
-                LLVMBuildFCmp(self.builder.unwrap(), LLVMRealPredicate::LLVMRealORD, a.value, b.value, into_c("").as_ptr())
-            }
-        }
+    pub fn build_float_is_ord(&self, a: FloatValue<'ctx>, b: FloatValue<'ctx>) -> Result<IntValue<'ctx>, Error> {
+        self.check(&[a.value, b.value])?;
+        Ok(IntValue {
+            base: unsafe {
+                value(LLVMBuildFCmp(self.builder.unwrap(), LLVMRealPredicate::LLVMRealORD, a.value, b.value, empty()))
+            },
+        })
     }
 
     /// Builds a check for an unordered float
-    pub fn build_float_non_ord(&self, a: Value, b: Value) -> Value {
-        Value {
-            value: unsafe {
-                LLVMBuildFCmp(self.builder.unwrap(), LLVMRealPredicate::LLVMRealUNO, a.value, b.value, into_c("").as_ptr())
-            }
-        }
+    pub fn build_float_non_ord(&self, a: FloatValue<'ctx>, b: FloatValue<'ctx>) -> Result<IntValue<'ctx>, Error> {
+        self.check(&[a.value, b.value])?;
+        Ok(IntValue {
+            base: unsafe {
+                value(LLVMBuildFCmp(self.builder.unwrap(), LLVMRealPredicate::LLVMRealUNO, a.value, b.value, empty()))
+            },
+        })
     }
 }
 
-impl Deref for Builder {
+impl<'ctx> Deref for Builder<'ctx> {
     type Target = LLVMBuilderRef;
 
     fn deref(&self) -> &LLVMBuilderRef {
@@ -788,7 +927,7 @@ impl Deref for Builder {
     }
 }
 
-impl Drop for Builder {
+impl<'ctx> Drop for Builder<'ctx> {
     fn drop(&mut self) {
         if let Some(builder) = self.builder {
             unsafe {
@@ -798,8 +937,31 @@ impl Drop for Builder {
     }
 }
 
-impl Debug for Builder {
+impl<'ctx> Debug for Builder<'ctx> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "Builder")
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reject_other_contexts() {
+        let context = Context::new();
+        let other = Context::new();
+        let module = context.create_module("test").unwrap();
+        let function = module.add_function("test", context.ty_void().function(vec![], false).unwrap()).unwrap();
+        let builder = context.create_builder();
+        builder.position_at_end(function.append_basic_block("entry").unwrap()).unwrap();
+        assert!(builder.build_alloca(context.ty_i32()).is_ok());
+        match builder.build_alloca(other.ty_i32()) {
+            Err(Error::InvalidArgument(_)) => (),
+            _ => panic!("expected an error for a type from another context"),
+        }
+        assert!(context.ty_struct(vec![other.ty_i8()], false).is_err());
+        assert!(context.ty_void().function(vec![other.ty_i8()], false).is_err());
+        assert!(module.add_global("global", other.ty_i8()).is_err());
+    }
 }
\ No newline at end of file
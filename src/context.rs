@@ -1,45 +1,484 @@
-//! A wrapper around the LLVM global context
+//! A wrapper around a `LLVMContextRef`
 
 use super::*;
 use super::c_api::*;
 
-/// Creates a new module with the given name in this context
-pub fn create_module<S>(name: S) -> Module where S: AsRef<str> {
-    Module {
-        module: Some(
-            unsafe {
-                LLVMModuleCreateWithNameInContext(into_c(name).as_ptr(), context())
+use buffer::MemoryBuffer;
+use libc::c_void;
+use llvm_sys::bit_reader::{LLVMParseBitcodeInContext2, LLVMGetBitcodeModuleInContext2};
+use llvm_sys::ir_reader::LLVMParseIRInContext;
+use std::fs;
+use std::mem;
+use std::path::Path;
+use std::ptr::null_mut;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// A wrapper around a `LLVMContextRef`
+///
+/// Modules, builders, types and values borrow the context they were created in, so they cannot
+/// outlive it. Contexts created with `Context::new` are disposed automatically when they leave
+/// scope, while `Context::global` refers to the global context provided by LLVM.
+///
+/// Combining types or values that were created in different contexts is detected at runtime and
+/// returns `Error::InvalidArgument`.
+///
+/// A context may be moved to another thread, but everything created in it stays on the thread
+/// that borrows it. Use a separate context for each thread that generates code.
+pub struct Context {
+    context: Option<LLVMContextRef>,
+}
+
+unsafe impl Send for Context {}
+
+/// Set once a thread has claimed the global context
+static GLOBAL_CLAIMED: AtomicBool = AtomicBool::new(false);
+
+thread_local! {
+    /// The global context, which is never disposed and can only be used by one thread
+    static GLOBAL: &'static Context = {
+        if GLOBAL_CLAIMED.swap(true, Ordering::SeqCst) {
+            panic!("the global context is already used by another thread");
+        }
+        Box::leak(Box::new(Context { context: None }))
+    };
+}
+
+impl Context {
+    /// Creates a new, empty context
+    pub fn new() -> Context {
+        Context {
+            context: Some(
+                unsafe {
+                    LLVMContextCreate()
+                }
+            )
+        }
+    }
+
+    /// Returns the global context provided by LLVM
+    ///
+    /// The global context belongs to the first thread that uses it. Panics if it is used from any
+    /// other thread.
+    pub fn global() -> &'static Context {
+        GLOBAL.with(|&global| global)
+    }
+
+    /// Creates a new module with the given name in this context
+    pub fn create_module<S>(&self, name: S) -> Result<Module<'_>, Error> where S: AsRef<str> {
+        let name = into_c(name)?;
+        Ok(Module {
+            module: Some(
+                unsafe {
+                    LLVMModuleCreateWithNameInContext(name.as_ptr(), self.inner())
+                }
+            ),
+            marker: PhantomData,
+        })
+    }
+
+    /// Parses a module from a string of LLVM IR
+    pub fn parse_ir_str<S>(&self, ir: S) -> Result<Module<'_>, Error> where S: AsRef<str> {
+        self.parse_ir("<string>", ir.as_ref())
+    }
+
+    /// Parses a module from a file containing LLVM IR
+    pub fn parse_ir_file<P>(&self, path: P) -> Result<Module<'_>, Error> where P: AsRef<Path> {
+        let path = path.as_ref();
+        let ir = fs::read_to_string(path)?;
+        self.parse_ir(&path.to_string_lossy(), &ir)
+    }
+
+    /// Parses a module from LLVM IR, using the given name in diagnostics
+    fn parse_ir(&self, name: &str, ir: &str) -> Result<Module<'_>, Error> {
+        Ok(Module {
+            module: Some(
+                unsafe {
+                    parse_ir_in_context(self.inner(), name, ir)?
+                }
+            ),
+            marker: PhantomData,
+        })
+    }
+
+    /// Reads a module from a bitcode file
+    pub fn read_bitcode<P>(&self, path: P) -> Result<Module<'_>, Error> where P: AsRef<Path> {
+        self.from_bitcode_bytes(&fs::read(path)?)
+    }
+
+    /// Reads a module from bitcode in memory
+    pub fn from_bitcode_bytes(&self, bytes: &[u8]) -> Result<Module<'_>, Error> {
+        let buffer = MemoryBuffer::from_bytes("<bitcode>", bytes)?;
+        unsafe {
+            let mut module = null_mut();
+            let (failed, diagnostics) = capture_diagnostics(self.inner(), || {
+                LLVMParseBitcodeInContext2(self.inner(), buffer.inner(), &mut module)
+            });
+            if failed != 0 {
+                Err(Error::Parse(diagnostics))
+            } else {
+                Ok(Module {
+                    module: Some(module),
+                    marker: PhantomData,
+                })
+            }
+        }
+    }
+
+    /// Reads a module from a bitcode file, loading function bodies only when they are needed
+    pub fn read_bitcode_lazy<P>(&self, path: P) -> Result<Module<'_>, Error> where P: AsRef<Path> {
+        self.from_bitcode_bytes_lazy(&fs::read(path)?)
+    }
+
+    /// Reads a module from bitcode in memory, loading function bodies only when they are needed
+    pub fn from_bitcode_bytes_lazy(&self, bytes: &[u8]) -> Result<Module<'_>, Error> {
+        let buffer = MemoryBuffer::from_bytes("<bitcode>", bytes)?;
+        unsafe {
+            let mut module = null_mut();
+            let (failed, diagnostics) = capture_diagnostics(self.inner(), || {
+                LLVMGetBitcodeModuleInContext2(self.inner(), buffer.inner(), &mut module)
+            });
+            if failed != 0 {
+                Err(Error::Parse(diagnostics))
+            } else {
+                // The module now owns the buffer
+                mem::forget(buffer);
+                Ok(Module {
+                    module: Some(module),
+                    marker: PhantomData,
+                })
             }
-        )
+        }
+    }
+
+    /// Creates a new builder in this context
+    pub fn create_builder<'ctx>(&'ctx self) -> Builder<'ctx> {
+        Builder {
+            builder: Some(
+                unsafe {
+                    LLVMCreateBuilderInContext(self.inner())
+                }
+            ),
+            context: self.inner(),
+            marker: PhantomData,
+        }
+    }
+
+    /// A constant struct with the given elements
+    pub fn const_struct<'ctx>(&'ctx self, elements: Vec<Value<'ctx>>, packed: bool) -> Result<Value<'ctx>, Error> {
+        Ok(Value {
+            value: unsafe {
+                let mut elements = val_vec(&elements);
+                for &element in &elements {
+                    check_context(self.inner(), LLVMTypeOf(element))?;
+                }
+                LLVMConstStructInContext(self.inner(), elements.as_mut_ptr(), elements.len() as u32, packed as i32)
+            },
+            marker: PhantomData,
+        })
+    }
+
+    /// A constant string with the given value
+    pub fn const_string<S>(&self, string: S, null_terminated: bool) -> Value<'_> where S: AsRef<str> {
+        Value {
+            value: unsafe {
+                let string = string.as_ref();
+                LLVMConstStringInContext(self.inner(), string.as_ptr() as *const i8, string.len() as u32, null_terminated as i32)
+            },
+            marker: PhantomData,
+        }
+    }
+
+    /// Create a named struct type
+    pub fn create_named_struct<S>(&self, name: S) -> Result<Type<'_>, Error> where S: AsRef<str> {
+        let name = into_c(name)?;
+        Ok(Type {
+            ty: unsafe {
+                LLVMStructCreateNamed(self.inner(), name.as_ptr())
+            },
+            marker: PhantomData,
+        })
+    }
+
+    /// A array type with a certain number of elements
+    pub fn ty_array<'ctx>(&'ctx self, ty: Type<'ctx>, count: u32) -> Result<Type<'ctx>, Error> {
+        Ok(Type {
+            ty: unsafe {
+                check_context(self.inner(), ty.ty)?;
+                LLVMArrayType(ty.ty, count)
+            },
+            marker: PhantomData,
+        })
+    }
+
+    /// A struct type with the given elements
+    pub fn ty_struct<'ctx>(&'ctx self, elements: Vec<Type<'ctx>>, packed: bool) -> Result<Type<'ctx>, Error> {
+        Ok(Type {
+            ty: unsafe {
+                let mut elements = ty_vec(&elements);
+                for &element in &elements {
+                    check_context(self.inner(), element)?;
+                }
+                LLVMStructTypeInContext(self.inner(), elements.as_mut_ptr(), elements.len() as u32, packed as i32)
+            },
+            marker: PhantomData,
+        })
+    }
+
+    /// The `void` type
+    pub fn ty_void(&self) -> Type<'_> {
+        Type {
+            ty: unsafe {
+                LLVMVoidTypeInContext(self.inner())
+            },
+            marker: PhantomData,
+        }
+    }
+
+    /// The `i1` type
+    pub fn ty_i1(&self) -> Type<'_> {
+        Type {
+            ty: unsafe {
+                LLVMInt1TypeInContext(self.inner())
+            },
+            marker: PhantomData,
+        }
+    }
+
+    /// The `i8` type
+    pub fn ty_i8(&self) -> Type<'_> {
+        Type {
+            ty: unsafe {
+                LLVMInt8TypeInContext(self.inner())
+            },
+            marker: PhantomData,
+        }
+    }
+
+    /// The `i16` type
+    pub fn ty_i16(&self) -> Type<'_> {
+        Type {
+            ty: unsafe {
+                LLVMInt16TypeInContext(self.inner())
+            },
+            marker: PhantomData,
+        }
+    }
+
+    /// The `i32` type
+    pub fn ty_i32(&self) -> Type<'_> {
+        Type {
+            ty: unsafe {
+                LLVMInt32TypeInContext(self.inner())
+            },
+            marker: PhantomData,
+        }
+    }
+
+    /// The `i64` type
+    pub fn ty_i64(&self) -> Type<'_> {
+        Type {
+            ty: unsafe {
+                LLVMInt64TypeInContext(self.inner())
+            },
+            marker: PhantomData,
+        }
+    }
+
+    /// The `i128` type
+    pub fn ty_i128(&self) -> Type<'_> {
+        Type {
+            ty: unsafe {
+                LLVMInt128TypeInContext(self.inner())
+            },
+            marker: PhantomData,
+        }
+    }
+
+    /// The `isize` type for a given data layout
+    pub fn ty_isize(&self, data: &target::TargetData) -> Type<'_> {
+        Type {
+            ty: unsafe {
+                llvm_sys::target::LLVMIntPtrTypeInContext(self.inner(), data.inner())
+            },
+            marker: PhantomData,
+        }
+    }
+
+    /// An integer type with any number of bits
+    pub fn ty_i(&self, bits: u32) -> Type<'_> {
+        Type {
+            ty: unsafe {
+                LLVMIntTypeInContext(self.inner(), bits)
+            },
+            marker: PhantomData,
+        }
+    }
+
+    /// The `half` type
+    pub fn ty_half(&self) -> Type<'_> {
+        Type {
+            ty: unsafe {
+                LLVMHalfTypeInContext(self.inner())
+            },
+            marker: PhantomData,
+        }
+    }
+
+    /// The `float` type
+    pub fn ty_float(&self) -> Type<'_> {
+        Type {
+            ty: unsafe {
+                LLVMFloatTypeInContext(self.inner())
+            },
+            marker: PhantomData,
+        }
+    }
+
+    /// The `double` type
+    pub fn ty_double(&self) -> Type<'_> {
+        Type {
+            ty: unsafe {
+                LLVMDoubleTypeInContext(self.inner())
+            },
+            marker: PhantomData,
+        }
+    }
+
+    /// The `fp128` type
+    pub fn ty_fp128(&self) -> Type<'_> {
+        Type {
+            ty: unsafe {
+                LLVMFP128TypeInContext(self.inner())
+            },
+            marker: PhantomData,
+        }
+    }
+
+    /// Returns the internal context reference
+    pub fn inner(&self) -> LLVMContextRef {
+        match self.context {
+            Some(context) => context,
+            None => unsafe { LLVMGetGlobalContext() },
+        }
+    }
+
+    /// Destroys the wrapper, returning the internal context reference
+    ///
+    /// # Safety
+    ///
+    /// The context is no longer disposed automatically, so the caller must dispose it. The global
+    /// context must not be disposed.
+    pub unsafe fn into_inner(self) -> LLVMContextRef {
+        let context = self.inner();
+        mem::forget(self);
+        context
     }
 }
 
-/// Creates a new builder in this context
-pub fn create_builder() -> Builder {
-    Builder {
-        builder: Some(
+impl Default for Context {
+    fn default() -> Context {
+        Context::new()
+    }
+}
+
+impl Drop for Context {
+    fn drop(&mut self) {
+        if let Some(context) = self.context {
             unsafe {
-                LLVMCreateBuilderInContext(context())
+                LLVMContextDispose(context);
             }
-        )
+        }
     }
 }
 
-/// A constant struct with the given elements
-pub fn const_struct(elements: Vec<Value>, packed: bool) -> Value {
-    Value {
-        value: unsafe {
-            LLVMConstStructInContext(context(), val_vec(&elements).as_mut_ptr(), elements.len() as u32, packed as i32)
+impl Debug for Context {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.context.is_some() {
+            write!(f, "Context")
+        } else {
+            write!(f, "Context(global)")
         }
     }
 }
 
+/// Creates a new module with the given name in the global context
+pub fn create_module<S>(name: S) -> Result<Module<'static>, Error> where S: AsRef<str> {
+    Context::global().create_module(name)
+}
+
+/// Creates a new builder in the global context
+pub fn create_builder<'ctx>() -> Builder<'ctx> {
+    Context::global().create_builder()
+}
+
+/// A constant struct with the given elements
+pub fn const_struct(elements: Vec<Value<'static>>, packed: bool) -> Result<Value<'static>, Error> {
+    Context::global().const_struct(elements, packed)
+}
+
 /// A constant string with the given value
-pub fn const_string<S>(string: S, null_terminated: bool) -> Value where S: AsRef<str> {
-    Value {
-        value: unsafe {
-            let string = string.as_ref();
-            LLVMConstStringInContext(context(), into_c(string).as_ptr(), string.len() as u32, null_terminated as i32)
+pub fn const_string<S>(string: S, null_terminated: bool) -> Value<'static> where S: AsRef<str> {
+    Context::global().const_string(string, null_terminated)
+}
+
+/// A diagnostic handler, which LLVM represents as null when none is set
+type DiagnosticHandler = Option<extern "C" fn(LLVMDiagnosticInfoRef, *mut c_void)>;
+
+// Declared here since the `llvm-sys` bindings don't allow the handler to be null
+extern "C" {
+    #[link_name = "LLVMContextGetDiagnosticHandler"]
+    fn get_diagnostic_handler(context: LLVMContextRef) -> DiagnosticHandler;
+    #[link_name = "LLVMContextSetDiagnosticHandler"]
+    fn set_diagnostic_handler(context: LLVMContextRef, handler: DiagnosticHandler, diagnostic_context: *mut c_void);
+}
+
+/// Restores the previous diagnostic handler of a context when it leaves scope, even on panic
+struct RestoreDiagnosticHandler {
+    context: LLVMContextRef,
+    handler: DiagnosticHandler,
+    diagnostic_context: *mut c_void,
+}
+
+impl Drop for RestoreDiagnosticHandler {
+    fn drop(&mut self) {
+        unsafe {
+            set_diagnostic_handler(self.context, self.handler, self.diagnostic_context);
         }
     }
+}
+
+/// Runs a function while collecting the messages reported to the diagnostic handler of a context
+///
+/// Some LLVM functions only report errors this way, and the default handler exits the process.
+pub(crate) unsafe fn capture_diagnostics<T, F>(context: LLVMContextRef, f: F) -> (T, Vec<Diagnostic>)
+    where F: FnOnce() -> T {
+    extern "C" fn handler(info: LLVMDiagnosticInfoRef, diagnostics: *mut c_void) {
+        unsafe {
+            let diagnostics = &mut *(diagnostics as *mut Vec<Diagnostic>);
+            diagnostics.push(Diagnostic::from_message(take_message(LLVMGetDiagInfoDescription(info))));
+        }
+    }
+    let mut diagnostics = Vec::new();
+    let result = {
+        let _restore = RestoreDiagnosticHandler {
+            context,
+            handler: get_diagnostic_handler(context),
+            diagnostic_context: LLVMContextGetDiagnosticContext(context),
+        };
+        set_diagnostic_handler(context, Some(handler), &mut diagnostics as *mut Vec<Diagnostic> as *mut c_void);
+        f()
+    };
+    (result, diagnostics)
+}
+
+/// Parses LLVM IR into a new module in the given context, using the given name in diagnostics
+pub(crate) unsafe fn parse_ir_in_context(context: LLVMContextRef, name: &str, ir: &str) -> Result<LLVMModuleRef, Error> {
+    let buffer = MemoryBuffer::from_bytes(name, ir.as_bytes())?;
+    let mut module = null_mut();
+    let mut error = null_mut();
+    if LLVMParseIRInContext(context, buffer.into_inner(), &mut module, &mut error) != 0 {
+        Err(Error::Parse(vec![Diagnostic::from_parser(name, &take_message(error))]))
+    } else {
+        Ok(module)
+    }
 }
\ No newline at end of file
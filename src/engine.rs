@@ -0,0 +1,707 @@
+//! Provides wrappers for running generated code in-process
+use super::*;
+use super::c_api::*;
+
+use libc::c_void;
+use llvm_sys::execution_engine::*;
+use llvm_sys::support::*;
+use llvm_sys::LLVMTypeKind::*;
+use std::collections::HashSet;
+use std::mem;
+use std::path::Path;
+use std::ptr::{null, null_mut};
+use std::sync::Once;
+use target::OptLevel;
+
+static LINK_IN_MCJIT: Once = Once::new();
+static LINK_IN_INTERPRETER: Once = Once::new();
+
+/// A wrapper around a `LLVMExecutionEngineRef` that owns the module it runs
+///
+/// An engine either compiles the module to native code with MCJIT, or evaluates its IR directly
+/// with the interpreter, which doesn't need a native target.
+pub struct ExecutionEngine<'ctx> {
+    engine: LLVMExecutionEngineRef,
+    module: LLVMModuleRef,
+    interpreter: bool,
+    mapped: HashSet<String>,
+    marker: PhantomData<&'ctx Context>,
+}
+
+impl<'ctx> ExecutionEngine<'ctx> {
+    /// Creates an MCJIT compiler for the native target that takes ownership of a module
+    pub fn new(module: Module<'ctx>) -> Result<ExecutionEngine<'ctx>, Error> {
+        ExecutionEngine::with_opt_level(module, OptLevel::Default)
+    }
+
+    /// Creates an MCJIT compiler with the given optimization level
+    pub fn with_opt_level(mut module: Module<'ctx>, level: OptLevel) -> Result<ExecutionEngine<'ctx>, Error> {
+        target::initialize_asm_printers()?;
+        LINK_IN_MCJIT.call_once(|| unsafe {
+            LLVMLinkInMCJIT();
+        });
+        let module = module.module.take().unwrap();
+        unsafe {
+            let mut options: LLVMMCJITCompilerOptions = mem::zeroed();
+            LLVMInitializeMCJITCompilerOptions(&mut options, mem::size_of::<LLVMMCJITCompilerOptions>());
+            options.OptLevel = level as u32;
+            let mut engine = null_mut();
+            let mut error = null_mut();
+            if LLVMCreateMCJITCompilerForModule(
+                &mut engine,
+                module,
+                &mut options,
+                mem::size_of::<LLVMMCJITCompilerOptions>(),
+                &mut error,
+            ) != 0 {
+                Err(Error::Engine(take_message(error)))
+            } else {
+                Ok(ExecutionEngine {
+                    engine,
+                    module,
+                    interpreter: false,
+                    mapped: HashSet::new(),
+                    marker: PhantomData,
+                })
+            }
+        }
+    }
+
+    /// Creates an interpreter that takes ownership of a module
+    pub fn interpreter(mut module: Module<'ctx>) -> Result<ExecutionEngine<'ctx>, Error> {
+        LINK_IN_INTERPRETER.call_once(|| unsafe {
+            LLVMLinkInInterpreter();
+        });
+        let module = module.module.take().unwrap();
+        unsafe {
+            let mut engine = null_mut();
+            let mut error = null_mut();
+            if LLVMCreateInterpreterForModule(&mut engine, module, &mut error) != 0 {
+                Err(Error::Engine(take_message(error)))
+            } else {
+                Ok(ExecutionEngine {
+                    engine,
+                    module,
+                    interpreter: true,
+                    mapped: HashSet::new(),
+                    marker: PhantomData,
+                })
+            }
+        }
+    }
+
+    /// Returns true if this engine is an interpreter
+    pub fn is_interpreter(&self) -> bool {
+        self.interpreter
+    }
+
+    /// Binds an external function declared in the module to a Rust function
+    ///
+    /// Returns an error if the module doesn't declare a function with this name, or if its type
+    /// doesn't match `F`.
+    pub fn map_function<F>(&mut self, name: &str, function: F) -> Result<(), Error> where F: JitSignature {
+        let value = self.find_function(name)?;
+        unsafe {
+            if !F::matches(ty(LLVMGetElementType(LLVMTypeOf(value)))) {
+                return Err(Error::Engine(format!("function {} does not have the signature of the mapped function", name)));
+            }
+            if !F::is_host_compatible() {
+                return Err(Error::Engine(format!("function {} cannot be mapped to a Rust function that uses bool", name)));
+            }
+            LLVMAddGlobalMapping(self.engine, value, function.address() as *mut c_void);
+        }
+        self.mapped.insert(name.to_owned());
+        Ok(())
+    }
+
+    /// Binds an external function or global declared in the module to an address
+    ///
+    /// Returns an error if the module doesn't declare a function or global with this name.
+    ///
+    /// # Safety
+    ///
+    /// The address must point to a value of the declared type, and must stay valid for as long
+    /// as the compiled code can use it.
+    pub unsafe fn add_global_mapping(&mut self, name: &str, address: *mut c_void) -> Result<(), Error> {
+        let c_name = into_c(name)?;
+        let mut value = LLVMGetNamedFunction(self.module, c_name.as_ptr());
+        if value.is_null() {
+            value = LLVMGetNamedGlobal(self.module, c_name.as_ptr());
+        }
+        if value.is_null() {
+            return Err(Error::Engine(format!("{} is not declared in the module", name)));
+        }
+        LLVMAddGlobalMapping(self.engine, value, address);
+        self.mapped.insert(name.to_owned());
+        Ok(())
+    }
+
+    /// Checks that every external function and global declared in the module can be resolved
+    ///
+    /// A declaration is resolved if it was mapped with `map_function` or `add_global_mapping`, or
+    /// if its symbol was added with `add_symbol` or found in a loaded library. LLVM aborts the
+    /// process when generated code uses a symbol that can't be resolved, so this is checked
+    /// before any code is compiled or run.
+    pub fn check_symbols(&self) -> Result<(), Error> {
+        unsafe {
+            let mut function = LLVMGetFirstFunction(self.module);
+            while !function.is_null() {
+                if LLVMIsDeclaration(function) != 0 && LLVMGetIntrinsicID(function) == 0 {
+                    self.check_symbol(function)?;
+                }
+                function = LLVMGetNextFunction(function);
+            }
+            let mut global = LLVMGetFirstGlobal(self.module);
+            while !global.is_null() {
+                if LLVMIsDeclaration(global) != 0 {
+                    self.check_symbol(global)?;
+                }
+                global = LLVMGetNextGlobal(global);
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks that a single declaration can be resolved
+    unsafe fn check_symbol(&self, value: LLVMValueRef) -> Result<(), Error> {
+        let name = from_c(LLVMGetValueName(value)).unwrap_or(String::new());
+        if self.mapped.contains(&name) || !LLVMSearchForAddressOfSymbol(into_c(&name)?.as_ptr()).is_null() {
+            Ok(())
+        } else {
+            Err(Error::Engine(format!("symbol {} could not be resolved", name)))
+        }
+    }
+
+    /// Compiles a function if necessary and returns its address
+    ///
+    /// The interpreter doesn't compile functions, so this always returns an error for it.
+    pub fn get_function_address<S>(&self, name: S) -> Result<u64, Error> where S: AsRef<str> {
+        let name = name.as_ref();
+        if self.interpreter {
+            return Err(Error::Engine(format!("function {} cannot be compiled by the interpreter", name)));
+        }
+        self.check_symbols()?;
+        let address = unsafe {
+            LLVMGetFunctionAddress(self.engine, into_c(name)?.as_ptr())
+        };
+        if address == 0 {
+            Err(Error::Engine(format!("function {} was not found", name)))
+        } else {
+            Ok(address)
+        }
+    }
+
+    /// Compiles a function if necessary and returns a pointer to it
+    ///
+    /// Returns an error if the function doesn't exist or if its type doesn't match `F`. The
+    /// returned function cannot outlive the engine, which owns the compiled code.
+    pub fn get_function<F>(&self, name: &str) -> Result<JitFunction<'_, F>, Error> where F: JitSignature {
+        let function = self.find_function(name)?;
+        unsafe {
+            if !F::matches(ty(LLVMGetElementType(LLVMTypeOf(function)))) {
+                return Err(Error::Engine(format!("function {} does not have the requested signature", name)));
+            }
+        }
+        let address = self.get_function_address(name)?;
+        Ok(JitFunction {
+            address: address as usize,
+            marker: PhantomData,
+        })
+    }
+
+    /// Runs a function with the given arguments and returns its result
+    ///
+    /// The interpreter can run any function. MCJIT can only run functions with no parameters and
+    /// functions with the signature of `main`, so an error is returned for any other function.
+    /// Use `get_function` to call those.
+    pub fn run_function<S>(&self, name: S, args: &[GenericValue]) -> Result<GenericValue, Error>
+        where S: AsRef<str> {
+        let name = name.as_ref();
+        let function = self.find_function(name)?;
+        unsafe {
+            let fn_ty = LLVMGetElementType(LLVMTypeOf(function));
+            if LLVMCountParamTypes(fn_ty) as usize != args.len() || LLVMIsFunctionVarArg(fn_ty) != 0 {
+                return Err(Error::Engine(format!("wrong number of arguments passed to function {}", name)));
+            }
+            if !self.interpreter && !mcjit_can_run(fn_ty) {
+                return Err(Error::Engine(format!("function {} cannot be run by MCJIT", name)));
+            }
+            self.check_symbols()?;
+            let mut args: Vec<LLVMGenericValueRef> = args.iter().map(|arg| arg.value).collect();
+            Ok(GenericValue {
+                value: LLVMRunFunction(self.engine, function, args.len() as u32, args.as_mut_ptr()),
+            })
+        }
+    }
+
+    /// Finds a function in the module owned by this engine
+    fn find_function(&self, name: &str) -> Result<LLVMValueRef, Error> {
+        let c_name = into_c(name)?;
+        unsafe {
+            let mut function = null_mut();
+            if LLVMFindFunction(self.engine, c_name.as_ptr(), &mut function) != 0 {
+                Err(Error::Engine(format!("function {} was not found", name)))
+            } else {
+                Ok(function)
+            }
+        }
+    }
+
+    /// Returns the internal execution engine reference
+    pub fn inner(&self) -> LLVMExecutionEngineRef {
+        self.engine
+    }
+}
+
+impl<'ctx> Deref for ExecutionEngine<'ctx> {
+    type Target = LLVMExecutionEngineRef;
+
+    fn deref(&self) -> &LLVMExecutionEngineRef {
+        &self.engine
+    }
+}
+
+impl<'ctx> Drop for ExecutionEngine<'ctx> {
+    fn drop(&mut self) {
+        unsafe {
+            LLVMDisposeExecutionEngine(self.engine);
+        }
+    }
+}
+
+impl<'ctx> Debug for ExecutionEngine<'ctx> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "ExecutionEngine")
+    }
+}
+
+/// Makes a symbol available to all execution engines, taking priority over loaded libraries
+///
+/// # Safety
+///
+/// The address must point to a value of the type the symbol is declared with, and must stay valid
+/// for the rest of the program, since the symbol can't be removed.
+pub unsafe fn add_symbol<S>(name: S, address: *mut c_void) -> Result<(), Error> where S: AsRef<str> {
+    LLVMAddSymbol(into_c(name)?.as_ptr(), address);
+    Ok(())
+}
+
+/// Searches the added symbols and loaded libraries for a symbol, returning its address
+pub fn search_for_address_of_symbol<S>(name: S) -> Option<*mut c_void> where S: AsRef<str> {
+    let name = into_c(name).ok()?;
+    let address = unsafe {
+        LLVMSearchForAddressOfSymbol(name.as_ptr())
+    };
+    if address.is_null() {
+        None
+    } else {
+        Some(address)
+    }
+}
+
+/// Loads a dynamic library so its symbols can be used by execution engines
+///
+/// If no path is given, the symbols of the running program are loaded instead.
+pub fn load_library_permanently(path: Option<&Path>) -> Result<(), Error> {
+    let c_path = match path {
+        Some(path) => Some(path_to_c(path)?),
+        None => None,
+    };
+    let failed = unsafe {
+        LLVMLoadLibraryPermanently(c_path.as_ref().map(|path| path.as_ptr()).unwrap_or(null())) != 0
+    };
+    match path {
+        Some(path) if failed => Err(Error::Engine(format!("could not load library {}", path.display()))),
+        None if failed => Err(Error::Engine("could not load the symbols of the program".to_owned())),
+        _ => Ok(()),
+    }
+}
+
+/// Returns true if MCJIT supports running a function of the given type with `LLVMRunFunction`
+unsafe fn mcjit_can_run(fn_ty: LLVMTypeRef) -> bool {
+    let ret = LLVMGetReturnType(fn_ty);
+    let mut params = vec![null_mut(); LLVMCountParamTypes(fn_ty) as usize];
+    LLVMGetParamTypes(fn_ty, params.as_mut_ptr());
+    let is_int = |ty, width| LLVMGetTypeKind(ty) == LLVMIntegerTypeKind && LLVMGetIntTypeWidth(ty) == width;
+    if params.is_empty() {
+        match LLVMGetTypeKind(ret) {
+            LLVMIntegerTypeKind => [1, 8, 16, 32, 64].contains(&LLVMGetIntTypeWidth(ret)),
+            LLVMVoidTypeKind | LLVMFloatTypeKind | LLVMDoubleTypeKind | LLVMPointerTypeKind => true,
+            _ => false,
+        }
+    } else {
+        params.len() <= 3 && is_int(ret, 32) && is_int(params[0], 32) &&
+            params[1..].iter().all(|&param| LLVMGetTypeKind(param) == LLVMPointerTypeKind)
+    }
+}
+
+/// A wrapper around a `LLVMGenericValueRef`, used to pass values to and from `run_function`
+pub struct GenericValue {
+    value: LLVMGenericValueRef,
+}
+
+impl GenericValue {
+    /// Creates a value holding an integer of the given type, which must be an integer type
+    pub fn from_int(ty: Type, value: u64, signed: bool) -> Result<GenericValue, Error> {
+        ty.check_kind(&[LLVMIntegerTypeKind], "an integer")?;
+        Ok(GenericValue {
+            value: unsafe {
+                LLVMCreateGenericValueOfInt(ty.ty, value as _, signed as i32)
+            },
+        })
+    }
+
+    /// Returns the integer held by this value
+    pub fn to_int(&self, signed: bool) -> u64 {
+        unsafe {
+            LLVMGenericValueToInt(self.value, signed as i32) as u64
+        }
+    }
+
+    /// Creates a value holding a signed integer of the given type, which must be an integer type
+    pub fn from_signed_int(ty: Type, value: i64) -> Result<GenericValue, Error> {
+        GenericValue::from_int(ty, value as u64, true)
+    }
+
+    /// Returns the signed integer held by this value
+    pub fn to_signed_int(&self) -> i64 {
+        self.to_int(true) as i64
+    }
+
+    /// Creates a value holding a floating point number of the given type
+    ///
+    /// The interpreter only supports `float` and `double` values, so other types are rejected.
+    pub fn from_float(ty: Type, value: f64) -> Result<GenericValue, Error> {
+        ty.check_kind(&[LLVMFloatTypeKind, LLVMDoubleTypeKind], "a float or double")?;
+        Ok(GenericValue {
+            value: unsafe {
+                LLVMCreateGenericValueOfFloat(ty.ty, value)
+            },
+        })
+    }
+
+    /// Returns the floating point number of the given type held by this value
+    ///
+    /// The type must be `float` or `double`, as for `from_float`.
+    pub fn to_float(&self, ty: Type) -> Result<f64, Error> {
+        ty.check_kind(&[LLVMFloatTypeKind, LLVMDoubleTypeKind], "a float or double")?;
+        Ok(unsafe {
+            LLVMGenericValueToFloat(ty.ty, self.value)
+        })
+    }
+
+    /// Creates a value holding a pointer
+    pub fn from_pointer<T>(pointer: *mut T) -> GenericValue {
+        GenericValue {
+            value: unsafe {
+                LLVMCreateGenericValueOfPointer(pointer as *mut c_void)
+            },
+        }
+    }
+
+    /// Returns the pointer held by this value
+    pub fn to_pointer<T>(&self) -> *mut T {
+        unsafe {
+            LLVMGenericValueToPointer(self.value) as *mut T
+        }
+    }
+
+    /// Returns the width in bits of the integer held by this value
+    pub fn int_width(&self) -> u32 {
+        unsafe {
+            LLVMGenericValueIntWidth(self.value)
+        }
+    }
+
+    /// Returns the internal generic value reference
+    pub fn inner(&self) -> LLVMGenericValueRef {
+        self.value
+    }
+
+    /// Destroys the wrapper, returning the internal generic value reference
+    ///
+    /// # Safety
+    ///
+    /// The value is no longer disposed automatically, so the caller must dispose it.
+    pub unsafe fn into_inner(self) -> LLVMGenericValueRef {
+        let value = self.value;
+        mem::forget(self);
+        value
+    }
+}
+
+impl Drop for GenericValue {
+    fn drop(&mut self) {
+        unsafe {
+            LLVMDisposeGenericValue(self.value);
+        }
+    }
+}
+
+impl Debug for GenericValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "GenericValue")
+    }
+}
+
+/// A function compiled by an `ExecutionEngine`, which cannot outlive it
+///
+/// The function is called with `call`, which converts the arguments and result as needed.
+#[derive(Copy, Clone)]
+pub struct JitFunction<'ee, F> {
+    pub(crate) address: usize,
+    pub(crate) marker: PhantomData<(&'ee (), F)>,
+}
+
+impl<'ee, F> Debug for JitFunction<'ee, F> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "JitFunction({:#x})", self.address)
+    }
+}
+
+/// A Rust type that can be passed to or returned from generated code
+///
+/// # Safety
+///
+/// `Abi` must have the representation of the LLVM types accepted by `matches` in the C calling
+/// convention, and `from_abi` must accept every value generated code can return.
+pub unsafe trait JitType: Sized {
+    /// The type used to pass values of this type to and from generated code
+    type Abi: Copy;
+
+    /// True if values are converted when passed to generated code, so the type can't be used by
+    /// Rust functions called from generated code
+    const CONVERTED: bool = false;
+
+    /// Returns true if this type has the same representation as the given LLVM type
+    fn matches(ty: Type) -> bool;
+
+    /// Converts a value received from generated code
+    fn from_abi(abi: Self::Abi) -> Self;
+
+    /// Converts a value to pass to generated code
+    fn into_abi(self) -> Self::Abi;
+}
+
+/// A function pointer type that describes the signature of generated code
+///
+/// # Safety
+///
+/// `matches` must only accept function types that can be called through this type, and
+/// `is_host_compatible` must only return true if generated code can call it with the same
+/// arguments.
+pub unsafe trait JitSignature: Copy {
+    /// Returns true if this signature matches the given function type
+    fn matches(ty: Type) -> bool;
+
+    /// Returns true if generated code can call a Rust function with this signature directly
+    fn is_host_compatible() -> bool;
+
+    /// Returns the address of this function
+    fn address(self) -> usize;
+}
+
+/// Implements `JitType` for types that are passed to generated code unchanged
+macro_rules! jit_abi {
+    () => {
+        type Abi = Self;
+
+        fn from_abi(abi: Self) -> Self {
+            abi
+        }
+
+        fn into_abi(self) -> Self {
+            self
+        }
+    };
+}
+
+macro_rules! jit_int {
+    ($($int:ty),*) => {
+        $(
+            unsafe impl JitType for $int {
+                jit_abi!();
+
+                fn matches(ty: Type) -> bool {
+                    unsafe {
+                        LLVMGetTypeKind(ty.ty) == LLVMIntegerTypeKind &&
+                            LLVMGetIntTypeWidth(ty.ty) as usize == mem::size_of::<$int>() * 8
+                    }
+                }
+            }
+        )*
+    };
+}
+
+jit_int!(i8, u8, i16, u16, i32, u32, i64, u64, isize, usize);
+
+/// An `i1`, which is passed as a `u8` since only its lowest bit is defined
+unsafe impl JitType for bool {
+    type Abi = u8;
+
+    const CONVERTED: bool = true;
+
+    fn matches(ty: Type) -> bool {
+        unsafe {
+            LLVMGetTypeKind(ty.ty) == LLVMIntegerTypeKind && LLVMGetIntTypeWidth(ty.ty) == 1
+        }
+    }
+
+    fn from_abi(abi: u8) -> bool {
+        abi & 1 != 0
+    }
+
+    fn into_abi(self) -> u8 {
+        self as u8
+    }
+}
+
+unsafe impl JitType for f32 {
+    jit_abi!();
+
+    fn matches(ty: Type) -> bool {
+        unsafe {
+            LLVMGetTypeKind(ty.ty) == LLVMFloatTypeKind
+        }
+    }
+}
+
+unsafe impl JitType for f64 {
+    jit_abi!();
+
+    fn matches(ty: Type) -> bool {
+        unsafe {
+            LLVMGetTypeKind(ty.ty) == LLVMDoubleTypeKind
+        }
+    }
+}
+
+unsafe impl JitType for () {
+    jit_abi!();
+
+    fn matches(ty: Type) -> bool {
+        unsafe {
+            LLVMGetTypeKind(ty.ty) == LLVMVoidTypeKind
+        }
+    }
+}
+
+unsafe impl<T> JitType for *const T {
+    jit_abi!();
+
+    fn matches(ty: Type) -> bool {
+        unsafe {
+            LLVMGetTypeKind(ty.ty) == LLVMPointerTypeKind
+        }
+    }
+}
+
+unsafe impl<T> JitType for *mut T {
+    jit_abi!();
+
+    fn matches(ty: Type) -> bool {
+        unsafe {
+            LLVMGetTypeKind(ty.ty) == LLVMPointerTypeKind
+        }
+    }
+}
+
+macro_rules! jit_signature {
+    ($($arg:ident $value:ident),*) => {
+        unsafe impl<R, $($arg),*> JitSignature for extern "C" fn($($arg),*) -> R
+            where R: JitType, $($arg: JitType),* {
+            fn matches(fn_ty: Type) -> bool {
+                let expected: &[fn(Type) -> bool] = &[$(<$arg as JitType>::matches),*];
+                unsafe {
+                    let mut params = vec![null_mut(); LLVMCountParamTypes(fn_ty.ty) as usize];
+                    LLVMGetParamTypes(fn_ty.ty, params.as_mut_ptr());
+                    LLVMIsFunctionVarArg(fn_ty.ty) == 0 &&
+                        R::matches(ty(LLVMGetReturnType(fn_ty.ty))) &&
+                        params.len() == expected.len() &&
+                        params.iter().zip(expected).all(|(&param, matches)| matches(ty(param)))
+                }
+            }
+
+            fn is_host_compatible() -> bool {
+                !R::CONVERTED $(&& !<$arg as JitType>::CONVERTED)*
+            }
+
+            fn address(self) -> usize {
+                self as usize
+            }
+        }
+
+        impl<'ee, R, $($arg),*> JitFunction<'ee, extern "C" fn($($arg),*) -> R>
+            where R: JitType, $($arg: JitType),* {
+            /// Calls the compiled function
+            ///
+            /// # Safety
+            ///
+            /// The generated code is not checked, so it must be safe to run with these arguments.
+            /// Any addresses it uses, such as those bound with `add_global_mapping`, must still be
+            /// valid.
+            pub unsafe fn call(&self, $($value: $arg),*) -> R {
+                let function: extern "C" fn($(<$arg as JitType>::Abi),*) -> R::Abi = mem::transmute(self.address);
+                R::from_abi(function($($value.into_abi()),*))
+            }
+        }
+    };
+}
+
+jit_signature!();
+jit_signature!(A a);
+jit_signature!(A a, B b);
+jit_signature!(A a, B b, C c);
+jit_signature!(A a, B b, C c, D d);
+jit_signature!(A a, B b, C c, D d, E e);
+jit_signature!(A a, B b, C c, D d, E e, F f);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const IR: &str = "
+        define i32 @add(i32 %a, i32 %b) {
+          %sum = add i32 %a, %b
+          ret i32 %sum
+        }
+
+        define i1 @not(i1 %x) {
+          %result = xor i1 %x, true
+          ret i1 %result
+        }
+    ";
+
+    #[test]
+    fn call_compiled_functions() {
+        let context = Context::new();
+        let engine = ExecutionEngine::new(context.parse_ir_str(IR).unwrap()).unwrap();
+        let add = engine.get_function::<extern "C" fn(i32, i32) -> i32>("add").unwrap();
+        assert_eq!(unsafe { add.call(2, 3) }, 5);
+        assert_eq!(unsafe { add.call(-7, 3) }, -4);
+        let not = engine.get_function::<extern "C" fn(bool) -> bool>("not").unwrap();
+        assert!(!unsafe { not.call(true) });
+        assert!(unsafe { not.call(false) });
+    }
+
+    #[test]
+    fn reject_mismatched_signatures() {
+        let context = Context::new();
+        let engine = ExecutionEngine::new(context.parse_ir_str(IR).unwrap()).unwrap();
+        assert!(engine.get_function::<extern "C" fn(i64, i64) -> i64>("add").is_err());
+        assert!(engine.get_function::<extern "C" fn(u8) -> u8>("not").is_err());
+        assert!(engine.get_function::<extern "C" fn()>("missing").is_err());
+    }
+
+    #[test]
+    fn generic_values_check_types() {
+        let context = Context::new();
+        assert_eq!(GenericValue::from_signed_int(context.ty_i32(), -5).unwrap().to_signed_int(), -5);
+        let value = GenericValue::from_float(context.ty_double(), 1.5).unwrap();
+        assert_eq!(value.to_float(context.ty_double()).unwrap(), 1.5);
+        assert!(value.to_float(context.ty_i32()).is_err());
+        assert!(GenericValue::from_int(context.ty_double(), 1, false).is_err());
+        assert!(GenericValue::from_float(context.ty_i32(), 1.0).is_err());
+        assert!(GenericValue::from_float(context.ty_fp128(), 1.0).is_err());
+    }
+}
\ No newline at end of file
@@ -0,0 +1,226 @@
+//! The error type returned by fallible operations and the diagnostics it carries
+
+use super::*;
+
+use std::error;
+use std::io;
+use std::path::PathBuf;
+
+/// An error returned by a fallible operation
+#[derive(Debug)]
+pub enum Error {
+    /// An IO operation failed
+    Io(io::Error),
+    /// A module or function failed verification
+    Verify(Vec<Diagnostic>),
+    /// IR or bitcode could not be parsed
+    Parse(Vec<Diagnostic>),
+    /// Modules could not be linked together
+    Link(Vec<Diagnostic>),
+    /// An execution engine could not be created or used
+    Engine(String),
+    /// A target could not be found or used
+    Target(String),
+    /// A pass could not be added or run
+    Pass(String),
+    /// A value did not have the type an operation expected
+    InvalidType(String),
+    /// An argument was not valid for an operation
+    InvalidArgument(String),
+    /// A name contained a null byte
+    InvalidName(String),
+    /// A path could not be passed to LLVM
+    InvalidPath(PathBuf),
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            &Error::Io(ref error) => write!(f, "{}", error),
+            &Error::Verify(ref diagnostics) => {
+                write!(f, "verification failed")?;
+                for diagnostic in diagnostics {
+                    write!(f, "\n{}", diagnostic)?;
+                }
+                Ok(())
+            },
+            &Error::Parse(ref diagnostics) => {
+                write!(f, "parsing failed")?;
+                for diagnostic in diagnostics {
+                    write!(f, "\n{}", diagnostic)?;
+                }
+                Ok(())
+            },
+            &Error::Link(ref diagnostics) => {
+                write!(f, "linking failed")?;
+                for diagnostic in diagnostics {
+                    write!(f, "\n{}", diagnostic)?;
+                }
+                Ok(())
+            },
+            &Error::Engine(ref message) => write!(f, "execution engine error: {}", message),
+            &Error::Target(ref message) => write!(f, "target error: {}", message),
+            &Error::Pass(ref message) => write!(f, "pass error: {}", message),
+            &Error::InvalidType(ref message) => write!(f, "invalid type: {}", message),
+            &Error::InvalidArgument(ref message) => write!(f, "invalid argument: {}", message),
+            &Error::InvalidName(ref name) => write!(f, "invalid name: {:?}", name),
+            &Error::InvalidPath(ref path) => write!(f, "invalid path: {:?}", path),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            &Error::Io(ref error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(error: io::Error) -> Error {
+        Error::Io(error)
+    }
+}
+
+/// A single problem reported by LLVM
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Diagnostic {
+    /// The message describing the problem
+    pub message: String,
+    /// The lines of IR printed along with the message, if any
+    pub context: Vec<String>,
+    /// The line the problem was found on, if known
+    pub line: Option<u32>,
+    /// The column the problem was found on, if known
+    pub column: Option<u32>,
+}
+
+impl Diagnostic {
+    /// Creates a diagnostic with only a message
+    pub(crate) fn from_message(message: String) -> Diagnostic {
+        Diagnostic {
+            message,
+            context: Vec::new(),
+            line: None,
+            column: None,
+        }
+    }
+
+    /// Splits the output of the verifier into diagnostics
+    ///
+    /// The verifier prints each message on its own line, usually ending with `!`, followed by the
+    /// values involved. Lines that don't look like a new message are added to the previous one.
+    pub(crate) fn from_verifier(output: &str) -> Vec<Diagnostic> {
+        let mut diagnostics: Vec<Diagnostic> = Vec::new();
+        for line in output.lines() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let is_message = !line.starts_with(char::is_whitespace) && line.ends_with('!');
+            if !is_message {
+                if let Some(diagnostic) = diagnostics.last_mut() {
+                    diagnostic.context.push(line.to_owned());
+                    continue;
+                }
+            }
+            diagnostics.push(Diagnostic::from_message(line.to_owned()));
+        }
+        diagnostics
+    }
+
+    /// Converts the output of the IR parser into a diagnostic
+    ///
+    /// The parser prints a single message in the form `name:line:column: error: message`,
+    /// followed by the source line and a marker for the column.
+    pub(crate) fn from_parser(name: &str, output: &str) -> Diagnostic {
+        let mut lines = output.lines();
+        let first = lines.next().unwrap_or("");
+        let context = lines.map(|line| line.to_owned()).collect();
+        let rest = if first.starts_with(name) && first[name.len()..].starts_with(':') {
+            &first[name.len() + 1..]
+        } else {
+            first
+        };
+        let mut parts = rest.splitn(3, ':');
+        match (parts.next().map(str::parse), parts.next().map(str::parse), parts.next()) {
+            (Some(Ok(line)), Some(Ok(column)), Some(message)) => {
+                let message = message.trim_start();
+                Diagnostic {
+                    message: message.trim_start_matches("error: ").to_owned(),
+                    context,
+                    line: Some(line),
+                    column: Some(column),
+                }
+            },
+            _ => Diagnostic {
+                message: first.to_owned(),
+                context,
+                line: None,
+                column: None,
+            },
+        }
+    }
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let (Some(line), Some(column)) = (self.line, self.column) {
+            write!(f, "{}:{}: ", line, column)?;
+        }
+        write!(f, "{}", self.message)?;
+        for line in &self.context {
+            write!(f, "\n{}", line)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parser_output_with_location() {
+        let output = "test.ll:3:12: error: expected type\n  %x = add i33q 1, 2\n           ^";
+        let diagnostic = Diagnostic::from_parser("test.ll", output);
+        assert_eq!(diagnostic.message, "expected type");
+        assert_eq!(diagnostic.line, Some(3));
+        assert_eq!(diagnostic.column, Some(12));
+        assert_eq!(diagnostic.context, vec!["  %x = add i33q 1, 2", "           ^"]);
+    }
+
+    #[test]
+    fn parser_output_with_colons_in_message() {
+        let diagnostic = Diagnostic::from_parser("a:b", "a:b:1:2: error: expected ':' here");
+        assert_eq!(diagnostic.message, "expected ':' here");
+        assert_eq!((diagnostic.line, diagnostic.column), (Some(1), Some(2)));
+    }
+
+    #[test]
+    fn parser_output_without_location() {
+        let diagnostic = Diagnostic::from_parser("test.ll", "something went wrong");
+        assert_eq!(diagnostic, Diagnostic::from_message("something went wrong".to_owned()));
+    }
+
+    #[test]
+    fn verifier_output_splits_on_messages() {
+        let output = "Both operands to a binary operator are not of the same type!\n  \
+                      %x = add i32 %a, i64 %b\nTerminator found in the middle of a basic block!\n\
+                      label %entry\n";
+        let diagnostics = Diagnostic::from_verifier(output);
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(diagnostics[0].message, "Both operands to a binary operator are not of the same type!");
+        assert_eq!(diagnostics[0].context, vec!["  %x = add i32 %a, i64 %b"]);
+        assert_eq!(diagnostics[1].message, "Terminator found in the middle of a basic block!");
+        assert_eq!(diagnostics[1].context, vec!["label %entry"]);
+    }
+
+    #[test]
+    fn verifier_output_without_exclamation_mark() {
+        let diagnostics = Diagnostic::from_verifier("Broken module found, compilation aborted\n\n");
+        assert_eq!(diagnostics, vec![Diagnostic::from_message("Broken module found, compilation aborted".to_owned())]);
+        assert!(Diagnostic::from_verifier("").is_empty());
+    }
+}
\ No newline at end of file
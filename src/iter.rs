@@ -1,24 +1,29 @@
 //! Provides iterators for various items
 use super::*;
 
+use llvm_sys::target_machine::{LLVMTargetRef, LLVMGetNextTarget};
+
 /// An iterator over functions in a module
 #[derive(Clone, Debug)]
-pub struct Functions {
-    pub(crate) pointer: Value,
+pub struct Functions<'ctx> {
+    pub(crate) pointer: Value<'ctx>,
 }
 
-impl Iterator for Functions {
-    type Item = Value;
+impl<'ctx> Iterator for Functions<'ctx> {
+    type Item = FunctionValue<'ctx>;
 
-    fn next(&mut self) -> Option<Value> {
+    fn next(&mut self) -> Option<FunctionValue<'ctx>> {
         if self.pointer.value.is_null() {
             None
         } else {
-            let next = self.pointer;
+            let next = FunctionValue {
+                base: self.pointer,
+            };
             self.pointer = Value {
                 value: unsafe {
                     LLVMGetNextFunction(self.pointer.value)
-                }
+                },
+                marker: PhantomData,
             };
             Some(next)
         }
@@ -27,22 +32,25 @@ impl Iterator for Functions {
 
 /// An iterator over global variables in a module
 #[derive(Clone, Debug)]
-pub struct Globals {
-    pub(crate) pointer: Value,
+pub struct Globals<'ctx> {
+    pub(crate) pointer: Value<'ctx>,
 }
 
-impl Iterator for Globals {
-    type Item = Value;
+impl<'ctx> Iterator for Globals<'ctx> {
+    type Item = GlobalValue<'ctx>;
 
-    fn next(&mut self) -> Option<Value> {
+    fn next(&mut self) -> Option<GlobalValue<'ctx>> {
         if self.pointer.value.is_null() {
             None
         } else {
-            let next = self.pointer;
+            let next = GlobalValue {
+                base: self.pointer,
+            };
             self.pointer = Value {
                 value: unsafe {
                     LLVMGetNextGlobal(self.pointer.value)
-                }
+                },
+                marker: PhantomData,
             };
             Some(next)
         }
@@ -51,14 +59,14 @@ impl Iterator for Globals {
 
 /// An iterator over parameters in a function
 #[derive(Clone, Debug)]
-pub struct Params {
-    pub(crate) pointer: Value,
+pub struct Params<'ctx> {
+    pub(crate) pointer: Value<'ctx>,
 }
 
-impl Iterator for Params {
-    type Item = Value;
+impl<'ctx> Iterator for Params<'ctx> {
+    type Item = Value<'ctx>;
 
-    fn next(&mut self) -> Option<Value> {
+    fn next(&mut self) -> Option<Value<'ctx>> {
         if self.pointer.value.is_null() {
             None
         } else {
@@ -66,7 +74,8 @@ impl Iterator for Params {
             self.pointer = Value {
                 value: unsafe {
                     LLVMGetNextParam(self.pointer.value)
-                }
+                },
+                marker: PhantomData,
             };
             Some(next)
         }
@@ -75,14 +84,14 @@ impl Iterator for Params {
 
 /// An iterator over basic blocks in a function
 #[derive(Clone, Debug)]
-pub struct Blocks {
-    pub(crate) pointer: BasicBlock,
+pub struct Blocks<'ctx> {
+    pub(crate) pointer: BasicBlock<'ctx>,
 }
 
-impl Iterator for Blocks {
-    type Item = BasicBlock;
+impl<'ctx> Iterator for Blocks<'ctx> {
+    type Item = BasicBlock<'ctx>;
 
-    fn next(&mut self) -> Option<BasicBlock> {
+    fn next(&mut self) -> Option<BasicBlock<'ctx>> {
         if self.pointer.basic_block.is_null() {
             None
         } else {
@@ -90,7 +99,32 @@ impl Iterator for Blocks {
             self.pointer = BasicBlock {
                 basic_block: unsafe {
                     LLVMGetNextBasicBlock(self.pointer.basic_block)
-                }
+                },
+                marker: PhantomData,
+            };
+            Some(next)
+        }
+    }
+}
+
+/// An iterator over the targets compiled into LLVM
+#[derive(Clone, Debug)]
+pub struct Targets {
+    pub(crate) target: LLVMTargetRef,
+}
+
+impl Iterator for Targets {
+    type Item = target::Target;
+
+    fn next(&mut self) -> Option<target::Target> {
+        if self.target.is_null() {
+            None
+        } else {
+            let next = target::Target {
+                target: self.target,
+            };
+            self.target = unsafe {
+                LLVMGetNextTarget(self.target)
             };
             Some(next)
         }
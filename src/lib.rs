@@ -6,8 +6,26 @@
 //! constraints on memory management. Mainly, immutable references can still be modified in order
 //! to make certain programs more simple.
 //!
-//! To make things simple, `LLVMBuilder` and `LLVMModule` are disposed automatically when they leave
-//! scope. Values and types are always created in the default global context provided by LLVM.
+//! Values and basic blocks borrow the module they belong to, so they cannot be used after the
+//! module is disposed. Functions, globals and blocks are deleted through a mutable borrow of their
+//! module, which guarantees that no handles to them are left behind.
+//!
+//! Operations that can fail, such as naming values, building instructions, writing files or
+//! looking up targets, return a `Result` with an `Error` instead of panicking.
+//!
+//! To make things simple, `LLVMContext`, `LLVMBuilder` and `LLVMModule` are disposed automatically
+//! when they leave scope. Modules, builders, values and types borrow the `Context` they were created
+//! in, so they cannot outlive it. The free functions such as `create_module` and `ty_i32` are
+//! shortcuts for the default global context provided by LLVM.
+//!
+//! Borrowing a context doesn't say which context something belongs to, so types and values from
+//! two contexts with the same lifetime can still meet. This is detected at runtime: functions
+//! that combine them, such as the `build_*` methods of `Builder`, return `Error::InvalidArgument`
+//! instead of passing them to LLVM.
+//!
+//! The global context belongs to the first thread that uses it, and using it from any other thread
+//! panics. To generate code on several threads, give each thread its own `Context`, or use
+//! `target::emit_parallel`.
 //!
 //! If necessary, it is possible to use the `inner` function to access the wrapped value, or
 //! `into_inner` to destroy the wrapper without disposing the contained value. Most types can also
@@ -21,6 +39,10 @@
 //! be sure to install a version of LLVM 5 and add `LLVM_SYS_50_PREFIX=/path/to/llvm` to your `PATH`
 //! environment variable.
 //!
+//! By default every target compiled into LLVM is initialized. To initialize fewer targets, or to
+//! use an LLVM build with fewer backends, disable the default `all-targets` feature and enable
+//! `native` or the features for specific architectures, such as `x86`, `aarch64` or `arm`.
+//!
 //! [LLVM Documentation] | [LLVM Language Reference] | [Rust Bindings]
 //!
 //! ## Example
@@ -29,30 +51,33 @@
 //! # extern crate llvm_wrap as llvm;
 //! # use llvm::*;
 //! # use llvm::types::*;
-//! # fn main() {
+//! # use std::convert::TryFrom;
+//! # fn main() { run().unwrap() }
+//! # fn run() -> Result<(), Error> {
 //! // Create a module
-//! let module = create_module("add");
+//! let module = create_module("add")?;
 //! // Create a builder
 //! let builder = create_builder();
 //!
 //! // Get an `i32 (i32, i32)` type
-//! let ty = ty_i32().function(vec![ty_i32(); 2], false);
+//! let ty = ty_i32().function(vec![ty_i32(); 2], false)?;
 //! // Create the add function
-//! let def = module.add_function("add", ty);
+//! let def = module.add_function("add", ty)?;
 //! // Add an entry block
-//! let entry = def.append_basic_block("entry");
+//! let entry = def.append_basic_block("entry")?;
 //! // Move the builder to the end of the block
-//! builder.position_at_end(entry);
+//! builder.position_at_end(entry)?;
+//! // Check that the two parameters are integers
+//! let a = IntValue::try_from(def.param(0).unwrap()).unwrap();
+//! let b = IntValue::try_from(def.param(1).unwrap()).unwrap();
 //! // Add and name the two parameters
-//! let result = builder.build_int_add(
-//!     def.param(0).name("a"),
-//!     def.param(1).name("b"),
-//! );
+//! let result = builder.build_int_add(a.name("a")?, b.name("b")?)?;
 //! // Return and name the result
-//! builder.build_ret(result.name("tmp"));
+//! builder.build_ret(result.name("tmp")?)?;
 //!
 //! // Dump the contents of the module
 //! module.dump();
+//! # Ok(())
 //! # }
 //! ```
 //!
@@ -75,6 +100,7 @@
 
 #![deny(missing_docs)]
 
+extern crate libc;
 extern crate llvm_sys;
 
 use llvm_sys::prelude::*;
@@ -82,19 +108,27 @@ use llvm_sys::core::*;
 use llvm_sys::*;
 
 use std::ffi::{CStr, CString};
+use std::path::Path;
+use std::marker::PhantomData;
 use std::ops::Deref;
 use std::fmt::{self, Debug, Display};
 
+mod buffer;
 mod context;
+mod error;
 mod module;
 mod builder;
 mod ty;
 mod bb;
 mod val;
+mod values;
 mod cc;
 mod link;
 
+pub mod engine;
 pub mod iter;
+pub mod orc;
+pub mod pass;
 pub mod target;
 pub mod types;
 
@@ -108,25 +142,37 @@ pub mod c_api {
     }
 
     /// Create a wrapper for a type
-    pub unsafe fn ty(ty: LLVMTypeRef) -> Type {
+    ///
+    /// # Safety
+    ///
+    /// The type must be valid and belong to a context that outlives `'ctx`.
+    pub unsafe fn ty<'ctx>(ty: LLVMTypeRef) -> Type<'ctx> {
         Type {
             ty,
+            marker: PhantomData,
         }
     }
 
     /// Create a wrapper for a value
-    pub unsafe fn value(value: LLVMValueRef) -> Value {
+    ///
+    /// # Safety
+    ///
+    /// The value must be valid and must not outlive `'ctx`, or the module it belongs to.
+    pub unsafe fn value<'ctx>(value: LLVMValueRef) -> Value<'ctx> {
         Value {
             value,
+            marker: PhantomData,
         }
     }
 
     /// Converts a `String` into a `CString`
     ///
     /// This function will also work with other, similar inputs like `&str` literals or
-    /// `String` references. Use the `as_ptr` method to pass a pointer to a C function.
-    pub fn into_c<S: AsRef<str>>(string: S) -> CString {
-        CString::new(string.as_ref()).expect("invalid name")
+    /// `String` references. Use the `as_ptr` method to pass a pointer to a C function. Returns
+    /// `Error::InvalidName` if the string contains a null byte.
+    pub fn into_c<S: AsRef<str>>(string: S) -> Result<CString, Error> {
+        let string = string.as_ref();
+        CString::new(string).map_err(|_| Error::InvalidName(string.to_owned()))
     }
 
     /// Converts a `*const i8` into a `String`, if possible
@@ -163,6 +209,8 @@ pub mod c_api {
 #[doc(inline)]
 pub use context::*;
 #[doc(inline)]
+pub use error::{Error, Diagnostic};
+#[doc(inline)]
 pub use module::Module;
 #[doc(inline)]
 pub use builder::Builder;
@@ -173,13 +221,58 @@ pub use bb::BasicBlock;
 #[doc(inline)]
 pub use val::Value;
 #[doc(inline)]
+pub use values::{
+    IntValue,
+    FloatValue,
+    PointerValue,
+    FunctionValue,
+    GlobalValue,
+    InstructionValue,
+    PhiValue,
+    BasicBlockValue
+};
+#[doc(inline)]
 pub use cc::CallConv;
 #[doc(inline)]
 pub use link::Linkage;
 
+/// Returns an empty C string for unnamed values
+fn empty() -> *const i8 {
+    b"\0".as_ptr() as *const i8
+}
+
+/// Converts a `Path` into a `CString`
+fn path_to_c(path: &Path) -> Result<CString, Error> {
+    path.to_str()
+        .and_then(|string| CString::new(string).ok())
+        .ok_or_else(|| Error::InvalidPath(path.to_path_buf()))
+}
+
+/// Takes ownership of a message allocated by LLVM, converting it into a `String`
+unsafe fn take_message(message: *mut i8) -> String {
+    if message.is_null() {
+        String::new()
+    } else {
+        let string = CStr::from_ptr(message).to_string_lossy().into_owned();
+        LLVMDisposeMessage(message);
+        string
+    }
+}
+
+/// Returns `Error::InvalidArgument` if a type doesn't belong to the given context
+///
+/// Types and values only borrow their context, so handles from two contexts can still meet.
+unsafe fn check_context(context: LLVMContextRef, ty: LLVMTypeRef) -> Result<(), Error> {
+    if LLVMGetTypeContext(ty) == context {
+        Ok(())
+    } else {
+        Err(Error::InvalidArgument("types and values from different contexts cannot be combined".to_owned()))
+    }
+}
+
 /// Converts a `Vec<Value>` into a `Vec<LLVMValueRef>`
-fn val_vec(vals: &Vec<Value>) -> Vec<LLVMValueRef> {
-    vals.iter().map(|i| i.value).collect()
+fn val_vec<'ctx, V>(vals: &Vec<V>) -> Vec<LLVMValueRef> where V: Into<Value<'ctx>> + Copy {
+    vals.iter().map(|&i| i.into().value).collect()
 }
 
 /// Converts a `Vec<Type>` into a `Vec<LLVMTypeRef>`
@@ -3,87 +3,386 @@
 use super::*;
 use super::c_api::*;
 
-use llvm_sys::bit_writer::LLVMWriteBitcodeToFile;
+use llvm_sys::analysis::*;
+use buffer::MemoryBuffer;
+use llvm_sys::bit_writer::{LLVMWriteBitcodeToFile, LLVMWriteBitcodeToMemoryBuffer};
+use llvm_sys::linker::LLVMLinkModules2;
+use std::convert::TryFrom;
+use std::io;
 use std::path::Path;
+use std::ptr::null_mut;
+use std::slice;
+use std::str;
 
 /// A wrapper around a `LLVMModuleRef` for a specific context
-pub struct Module {
+///
+/// Handles to the functions, globals, instructions and basic blocks of a module borrow it, so they
+/// cannot be used once the module is disposed.
+pub struct Module<'ctx> {
     pub(crate) module: Option<LLVMModuleRef>,
+    pub(crate) marker: PhantomData<&'ctx Context>,
 }
 
-impl Module {
+impl Module<'static> {
+    /// Parses a module in the global context from a string of LLVM IR
+    pub fn parse_ir_str<S>(ir: S) -> Result<Module<'static>, Error> where S: AsRef<str> {
+        Context::global().parse_ir_str(ir)
+    }
+
+    /// Parses a module in the global context from a file containing LLVM IR
+    pub fn parse_ir_file<P>(path: P) -> Result<Module<'static>, Error> where P: AsRef<Path> {
+        Context::global().parse_ir_file(path)
+    }
+
+    /// Reads a module in the global context from a bitcode file
+    pub fn read_bitcode<P>(path: P) -> Result<Module<'static>, Error> where P: AsRef<Path> {
+        Context::global().read_bitcode(path)
+    }
+
+    /// Reads a module in the global context from bitcode in memory
+    pub fn from_bitcode_bytes(bytes: &[u8]) -> Result<Module<'static>, Error> {
+        Context::global().from_bitcode_bytes(bytes)
+    }
+
+    /// Reads a module in the global context from a bitcode file, loading function bodies lazily
+    pub fn read_bitcode_lazy<P>(path: P) -> Result<Module<'static>, Error> where P: AsRef<Path> {
+        Context::global().read_bitcode_lazy(path)
+    }
+
+    /// Reads a module in the global context from bitcode in memory, loading function bodies lazily
+    pub fn from_bitcode_bytes_lazy(bytes: &[u8]) -> Result<Module<'static>, Error> {
+        Context::global().from_bitcode_bytes_lazy(bytes)
+    }
+}
+
+impl<'ctx> Module<'ctx> {
     /// Add a function to the module
-    pub fn add_function<S>(&self, name: S, ty: Type) -> Value where S: AsRef<str> {
-        Value {
-            value: unsafe {
-                LLVMAddFunction(self.module.unwrap(), into_c(name).as_ptr(), ty.ty)
-            }
-        }
+    pub fn add_function<'m, S>(&'m self, name: S, ty: Type<'ctx>) -> Result<FunctionValue<'m>, Error> where S: AsRef<str> {
+        let name = into_c(name)?;
+        Ok(FunctionValue {
+            base: unsafe {
+                check_context(LLVMGetModuleContext(self.module.unwrap()), ty.ty)?;
+                value(LLVMAddFunction(self.module.unwrap(), name.as_ptr(), ty.ty))
+            },
+        })
     }
 
     /// Add a global to the module
-    pub fn add_global<S>(&self, name: S, ty: Type) -> Value where S: AsRef<str> {
-        Value {
+    pub fn add_global<'m, S>(&'m self, name: S, ty: Type<'ctx>) -> Result<GlobalValue<'m>, Error> where S: AsRef<str> {
+        let name = into_c(name)?;
+        Ok(GlobalValue {
+            base: unsafe {
+                check_context(LLVMGetModuleContext(self.module.unwrap()), ty.ty)?;
+                value(LLVMAddGlobal(self.module.unwrap(), ty.ty, name.as_ptr()))
+            },
+        })
+    }
+
+    /// Get the function with the given name, if it exists
+    pub fn get_function<'m, S>(&'m self, name: S) -> Option<FunctionValue<'m>> where S: AsRef<str> {
+        let name = into_c(name).ok()?;
+        FunctionValue::try_from(Value {
             value: unsafe {
-                LLVMAddGlobal(self.module.unwrap(), ty.ty, into_c(name).as_ptr())
+                LLVMGetNamedFunction(self.module.unwrap(), name.as_ptr())
+            },
+            marker: PhantomData,
+        }).ok()
+    }
+
+    /// Get the global with the given name, if it exists
+    pub fn get_global<'m, S>(&'m self, name: S) -> Option<GlobalValue<'m>> where S: AsRef<str> {
+        let name = into_c(name).ok()?;
+        GlobalValue::try_from(Value {
+            value: unsafe {
+                LLVMGetNamedGlobal(self.module.unwrap(), name.as_ptr())
+            },
+            marker: PhantomData,
+        }).ok()
+    }
+
+    /// Delete the function with the given name, returning true if it existed
+    ///
+    /// This requires a mutable borrow, so no handles to values or blocks in this module can be
+    /// used after the function is deleted. A function that is still used, such as by a call,
+    /// can't be deleted. Unnamed functions can't be found here, use the unsafe
+    /// `FunctionValue::delete_function` for them instead.
+    pub fn delete_function<S>(&mut self, name: S) -> Result<bool, Error> where S: AsRef<str> {
+        let name = match into_c(name) {
+            Ok(name) => name,
+            Err(_) => return Ok(false),
+        };
+        unsafe {
+            let function = LLVMGetNamedFunction(self.module.unwrap(), name.as_ptr());
+            if function.is_null() {
+                Ok(false)
+            } else if !LLVMGetFirstUse(function).is_null() {
+                Err(Error::InvalidArgument(format!("function {:?} is still used", name)))
+            } else {
+                LLVMDeleteFunction(function);
+                Ok(true)
             }
         }
     }
 
-    /// Get the function with the given name
-    pub fn get_function<S>(&self, name: S) -> Value where S: AsRef<str> {
-        Value {
-            value: unsafe {
-                LLVMGetNamedFunction(self.module.unwrap(), into_c(name).as_ptr())
+    /// Delete the global with the given name, returning true if it existed
+    ///
+    /// This requires a mutable borrow, so no handles to values or blocks in this module can be
+    /// used after the global is deleted. A global that is still used can't be deleted. Unnamed
+    /// globals can't be found here, use the unsafe `GlobalValue::delete_global` for them instead.
+    pub fn delete_global<S>(&mut self, name: S) -> Result<bool, Error> where S: AsRef<str> {
+        let name = match into_c(name) {
+            Ok(name) => name,
+            Err(_) => return Ok(false),
+        };
+        unsafe {
+            let global = LLVMGetNamedGlobal(self.module.unwrap(), name.as_ptr());
+            if global.is_null() {
+                Ok(false)
+            } else if !LLVMGetFirstUse(global).is_null() {
+                Err(Error::InvalidArgument(format!("global {:?} is still used", name)))
+            } else {
+                LLVMDeleteGlobal(global);
+                Ok(true)
             }
         }
     }
 
-    /// Get the global with the given name
-    pub fn get_global<S>(&self, name: S) -> Value where S: AsRef<str> {
-        Value {
-            value: unsafe {
-                LLVMGetNamedGlobal(self.module.unwrap(), into_c(name).as_ptr())
+    /// Delete the basic block with the given name from a function, returning true if it existed
+    ///
+    /// This requires a mutable borrow, so no handles to values or blocks in this module can be
+    /// used after the block is deleted. A block that is still branched to, or that defines values
+    /// used by other blocks, can't be deleted. Unnamed blocks can't be found here, use the unsafe
+    /// `BasicBlock::delete` for them instead.
+    pub fn delete_block<S, T>(&mut self, function: S, block: T) -> Result<bool, Error>
+        where S: AsRef<str>, T: AsRef<str> {
+        let block = block.as_ref();
+        let function = match self.get_function(function) {
+            Some(function) => function,
+            None => return Ok(false),
+        };
+        let found = function.blocks()
+            .find(|bb| bb.get_name().as_deref() == Some(block))
+            .map(|bb| bb.basic_block);
+        match found {
+            Some(bb) => {
+                unsafe {
+                    if !LLVMGetFirstUse(LLVMBasicBlockAsValue(bb)).is_null() || used_outside(bb) {
+                        return Err(Error::InvalidArgument(format!("block {:?} is still used", block)));
+                    }
+                    LLVMDeleteBasicBlock(bb);
+                }
+                Ok(true)
+            },
+            None => Ok(false),
+        }
+    }
+
+    /// Creates a copy of this module in the same context
+    ///
+    /// Functions and globals in the copy have the same names as in the original, so they can be
+    /// found with `get_function`, `get_global` or `counterpart_in`.
+    pub fn clone_module(&self) -> Module<'ctx> {
+        Module {
+            module: Some(
+                unsafe {
+                    LLVMCloneModule(self.module.unwrap())
+                }
+            ),
+            marker: PhantomData,
+        }
+    }
+
+    /// Link another module into this one, consuming it
+    ///
+    /// Both modules must belong to the same context.
+    pub fn link_in(&self, mut other: Module<'ctx>) -> Result<(), Error> {
+        unsafe {
+            let module = self.module.unwrap();
+            if LLVMGetModuleContext(module) != LLVMGetModuleContext(other.inner()) {
+                return Err(Error::Link(vec![
+                    Diagnostic::from_message("modules belong to different contexts".to_owned())
+                ]));
+            }
+            let other = other.module.take().unwrap();
+            let (failed, diagnostics) = capture_diagnostics(LLVMGetModuleContext(module), || {
+                LLVMLinkModules2(module, other) != 0
+            });
+            if failed {
+                Err(Error::Link(diagnostics))
+            } else {
+                Ok(())
             }
         }
     }
 
+    /// Link several modules into this one, consuming them
+    ///
+    /// Stops at the first module that could not be linked.
+    pub fn link_all<I>(&self, modules: I) -> Result<(), Error> where I: IntoIterator<Item = Module<'ctx>> {
+        for module in modules {
+            self.link_in(module)?;
+        }
+        Ok(())
+    }
+
     /// Sets the target triple for this module
-    pub fn set_triple<S>(&self, triple: S) where S: AsRef<str> {
+    pub fn set_triple<S>(&self, triple: S) -> Result<(), Error> where S: AsRef<str> {
+        let triple = into_c(triple)?;
+        unsafe {
+            LLVMSetTarget(self.module.unwrap(), triple.as_ptr());
+        }
+        Ok(())
+    }
+
+    /// Returns the target triple of this module, which is `unknown-unknown-unknown` if none has been
+    /// set
+    pub fn triple(&self) -> target::Triple {
+        target::Triple::normalized(&unsafe {
+            from_c(LLVMGetTarget(self.module.unwrap())).unwrap_or(String::new())
+        })
+    }
+
+    /// Returns the data layout of this module
+    pub fn data_layout(&self) -> target::TargetData {
         unsafe {
-            LLVMSetTarget(self.module.unwrap(), into_c(triple).as_ptr())
+            target::TargetData::from_inner(llvm_sys::target::LLVMCreateTargetData(LLVMGetDataLayoutStr(self.module.unwrap())))
+        }
+    }
+
+    /// Returns the data layout string of this module, which is empty if none has been set
+    pub fn data_layout_str(&self) -> String {
+        unsafe {
+            from_c(LLVMGetDataLayoutStr(self.module.unwrap())).unwrap_or(String::new())
         }
     }
 
     /// Sets the data layout for this module
     pub fn set_data_layout(&self, data: &target::TargetData) {
         unsafe {
-            LLVMSetDataLayout(self.module.unwrap(), into_c(data.to_string()).as_ptr())
+            let layout = llvm_sys::target::LLVMCopyStringRepOfTargetData(data.inner());
+            LLVMSetDataLayout(self.module.unwrap(), layout);
+            LLVMDisposeMessage(layout);
+        }
+    }
+
+    /// Returns the identifier of this module
+    pub fn identifier(&self) -> String {
+        unsafe {
+            let mut len = 0;
+            let identifier = LLVMGetModuleIdentifier(self.module.unwrap(), &mut len);
+            String::from_utf8_lossy(slice::from_raw_parts(identifier as *const u8, len)).into_owned()
+        }
+    }
+
+    /// Sets the identifier of this module
+    pub fn set_identifier<S>(&self, identifier: S) where S: AsRef<str> {
+        let identifier = identifier.as_ref();
+        unsafe {
+            LLVMSetModuleIdentifier(self.module.unwrap(), identifier.as_ptr() as *const i8, identifier.len());
+        }
+    }
+
+    /// Returns the name of the source file this module was created from
+    ///
+    /// The C API of LLVM 5 has no accessor for the source filename, so this prints the module and
+    /// reads it from the IR. It cannot be changed for the same reason.
+    pub fn source_filename(&self) -> String {
+        self.print_to_string()
+            .lines()
+            .filter_map(|line| ir_string_after(line, "source_filename = "))
+            .next()
+            .unwrap_or(String::new())
+    }
+
+    /// Returns the module-level inline assembly of this module
+    ///
+    /// The C API of LLVM 5 has no accessor for the inline assembly, so this prints the module and
+    /// reads it from the IR.
+    pub fn inline_asm(&self) -> String {
+        self.print_to_string()
+            .lines()
+            .filter_map(|line| ir_string_after(line, "module asm "))
+            .map(|line| line + "\n")
+            .collect()
+    }
+
+    /// Replaces the module-level inline assembly of this module
+    pub fn set_inline_asm<S>(&self, asm: S) -> Result<(), Error> where S: AsRef<str> {
+        let asm = into_c(asm)?;
+        unsafe {
+            LLVMSetModuleInlineAsm(self.module.unwrap(), asm.as_ptr());
         }
+        Ok(())
+    }
+
+    /// Appends to the module-level inline assembly of this module
+    ///
+    /// This reads the current inline assembly with `inline_asm` first.
+    pub fn append_inline_asm<S>(&self, asm: S) -> Result<(), Error> where S: AsRef<str> {
+        self.set_inline_asm(self.inline_asm() + asm.as_ref())
     }
 
     /// Returns an iterator over all functions in the module
-    pub fn functions(&self) -> iter::Functions {
+    pub fn functions<'m>(&'m self) -> iter::Functions<'m> {
         iter::Functions {
             pointer: Value {
                 value: unsafe {
                     LLVMGetFirstFunction(self.module.unwrap())
-                }
+                },
+                marker: PhantomData,
             }
         }
     }
 
     /// Returns an iterator over all globals in the module
-    pub fn globals(&self) -> iter::Globals {
+    pub fn globals<'m>(&'m self) -> iter::Globals<'m> {
         iter::Globals {
             pointer: Value {
                 value: unsafe {
                     LLVMGetFirstGlobal(self.module.unwrap())
-                }
+                },
+                marker: PhantomData,
             }
         }
     }
 
+    /// Verify that the module is well formed, returning the problems found if it isn't
+    pub fn verify(&self) -> Result<(), Error> {
+        unsafe {
+            let mut message = null_mut();
+            let broken = LLVMVerifyModule(
+                self.module.unwrap(),
+                LLVMVerifierFailureAction::LLVMReturnStatusAction,
+                &mut message,
+            ) != 0;
+            let message = take_message(message);
+            if broken {
+                Err(Error::Verify(Diagnostic::from_verifier(&message)))
+            } else {
+                Ok(())
+            }
+        }
+    }
+
+    /// Run the given passes on the module, returning true if it was changed
+    pub fn optimize(&mut self, passes: &[pass::Pass]) -> bool {
+        pass::PassManager::with_passes(passes).run(self)
+    }
+
+    /// Run the standard optimization pipeline on the module, returning true if it was changed
+    ///
+    /// The function passes are run on every function first, followed by the module passes.
+    pub fn optimize_with(&mut self, builder: &pass::PassManagerBuilder) -> bool {
+        let changed = {
+            let functions = pass::FunctionPassManager::new(self);
+            builder.populate_function(&functions);
+            functions.run_all()
+        };
+        let modules = pass::PassManager::new();
+        builder.populate_module(&modules);
+        modules.run(self) || changed
+    }
+
     /// Dump the contents of the module to stderr
     pub fn dump(&self) {
         unsafe {
@@ -92,36 +391,59 @@ impl Module {
     }
 
     /// Write module IR to a file
-    pub fn write_llvm_ir<P>(&self, path: P) where P: AsRef<Path> {
+    pub fn write_llvm_ir<P>(&self, path: P) -> Result<(), Error> where P: AsRef<Path> {
+        let path = path_to_c(path.as_ref())?;
         unsafe {
-            if LLVMPrintModuleToFile(
-                self.module.unwrap(),
-                into_c(path.as_ref()
-                    .to_str()
-                    .expect("path could not be converted to string")
-                ).as_ptr(),
-                vec![into_c("could not output LLVM IR for module").as_ptr() as *mut i8].as_mut_ptr(),
-            ) != 0 {
-                panic!("failed to write LLVM IR to file");
+            let mut error = null_mut();
+            if LLVMPrintModuleToFile(self.module.unwrap(), path.as_ptr(), &mut error) != 0 {
+                Err(Error::Io(io::Error::new(io::ErrorKind::Other, take_message(error))))
+            } else {
+                Ok(())
             }
         }
     }
 
     /// Write module bitcode to a file
-    pub fn write_bitcode<P>(&self, path: P) where P: AsRef<Path> {
+    pub fn write_bitcode<P>(&self, path: P) -> Result<(), Error> where P: AsRef<Path> {
+        let c_path = path_to_c(path.as_ref())?;
         unsafe {
-            if LLVMWriteBitcodeToFile(
-                self.module.unwrap(),
-                into_c(path.as_ref()
-                    .to_str()
-                    .expect("path could not be converted to string")
-                ).as_ptr()
-            ) != 0 {
-                panic!("failed to write bitcode to file");
+            if LLVMWriteBitcodeToFile(self.module.unwrap(), c_path.as_ptr()) != 0 {
+                Err(Error::Io(io::Error::new(
+                    io::ErrorKind::Other,
+                    format!("could not write bitcode to {}", path.as_ref().display()),
+                )))
+            } else {
+                Ok(())
             }
         }
     }
 
+    /// Write module IR to a writer
+    pub fn write_llvm_ir_to<W>(&self, mut writer: W) -> Result<(), Error> where W: io::Write {
+        writer.write_all(self.print_to_string().as_bytes())?;
+        Ok(())
+    }
+
+    /// Write module bitcode to a writer
+    pub fn write_bitcode_to<W>(&self, mut writer: W) -> Result<(), Error> where W: io::Write {
+        writer.write_all(&self.to_bitcode_bytes())?;
+        Ok(())
+    }
+
+    /// Returns the module IR as a string
+    pub fn print_to_string(&self) -> String {
+        unsafe {
+            take_message(LLVMPrintModuleToString(self.module.unwrap()))
+        }
+    }
+
+    /// Returns the module bitcode
+    pub fn to_bitcode_bytes(&self) -> Vec<u8> {
+        unsafe {
+            MemoryBuffer::from_inner(LLVMWriteBitcodeToMemoryBuffer(self.module.unwrap())).as_bytes().to_vec()
+        }
+    }
+
     /// Returns the internal module reference
     pub fn inner(&self) -> LLVMModuleRef {
         self.module.unwrap()
@@ -133,7 +455,7 @@ impl Module {
     }
 }
 
-impl Deref for Module {
+impl<'ctx> Deref for Module<'ctx> {
     type Target = LLVMModuleRef;
 
     fn deref(&self) -> &LLVMModuleRef {
@@ -141,7 +463,7 @@ impl Deref for Module {
     }
 }
 
-impl Drop for Module {
+impl<'ctx> Drop for Module<'ctx> {
     fn drop(&mut self) {
         if let Some(module) = self.module {
             unsafe {
@@ -151,8 +473,204 @@ impl Drop for Module {
     }
 }
 
-impl Debug for Module {
+impl<'ctx> Clone for Module<'ctx> {
+    fn clone(&self) -> Module<'ctx> {
+        self.clone_module()
+    }
+}
+
+impl<'ctx> Display for Module<'ctx> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.print_to_string())
+    }
+}
+
+impl<'ctx> Debug for Module<'ctx> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "Module")
     }
+}
+
+/// Returns the string in a line of IR that starts with the given prefix
+/// Checks if any instruction in a block is used by an instruction in another block
+unsafe fn used_outside(bb: LLVMBasicBlockRef) -> bool {
+    let mut instruction = LLVMGetFirstInstruction(bb);
+    while !instruction.is_null() {
+        let mut use_ = LLVMGetFirstUse(instruction);
+        while !use_.is_null() {
+            if LLVMGetInstructionParent(LLVMGetUser(use_)) != bb {
+                return true;
+            }
+            use_ = LLVMGetNextUse(use_);
+        }
+        instruction = LLVMGetNextInstruction(instruction);
+    }
+    false
+}
+
+fn ir_string_after(line: &str, prefix: &str) -> Option<String> {
+    if let Some(string) = line.strip_prefix(prefix) {
+        let string = string.trim_end();
+        if string.len() >= 2 && string.starts_with('"') && string.ends_with('"') {
+            return Some(unescape_ir_string(&string[1..string.len() - 1]));
+        }
+    }
+    None
+}
+
+/// Reverses the escaping of a quoted string printed in LLVM IR
+fn unescape_ir_string(string: &str) -> String {
+    let bytes = string.as_bytes();
+    let mut unescaped = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'\\' && i + 2 < bytes.len() {
+            let digits = str::from_utf8(&bytes[i + 1..i + 3]).unwrap_or("");
+            if let Ok(byte) = u8::from_str_radix(digits, 16) {
+                unescaped.push(byte);
+                i += 3;
+                continue;
+            }
+        }
+        unescaped.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&unescaped).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ADD: &str = "
+        define i32 @add(i32 %a, i32 %b) {
+          %sum = add i32 %a, %b
+          ret i32 %sum
+        }
+    ";
+
+    const CALL_ADD: &str = "
+        declare i32 @add(i32, i32)
+
+        define i32 @twice(i32 %a) {
+          %result = call i32 @add(i32 %a, i32 %a)
+          ret i32 %result
+        }
+    ";
+
+    #[test]
+    fn link_in_consumes_module() {
+        let context = Context::new();
+        let module = context.parse_ir_str(CALL_ADD).unwrap();
+        module.link_in(context.parse_ir_str(ADD).unwrap()).unwrap();
+        assert!(module.get_function("twice").is_some());
+        assert!(module.get_function("add").is_some());
+        module.verify().unwrap();
+    }
+
+    #[test]
+    fn link_in_reports_conflicts() {
+        let context = Context::new();
+        let module = context.parse_ir_str(ADD).unwrap();
+        match module.link_in(context.parse_ir_str(ADD).unwrap()) {
+            Err(Error::Link(diagnostics)) => {
+                assert!(diagnostics.iter().any(|diagnostic| diagnostic.message.contains("add")));
+            },
+            _ => panic!("expected a link error for a duplicate definition"),
+        }
+    }
+
+    #[test]
+    fn bitcode_round_trip() {
+        let context = Context::new();
+        let bytes = context.parse_ir_str(ADD).unwrap().to_bitcode_bytes();
+        let module = context.from_bitcode_bytes(&bytes).unwrap();
+        assert!(module.get_function("add").is_some());
+        module.verify().unwrap();
+        let lazy = context.from_bitcode_bytes_lazy(&bytes).unwrap();
+        assert!(lazy.get_function("add").is_some());
+    }
+
+    #[test]
+    fn malformed_bitcode_is_a_parse_error() {
+        let context = Context::new();
+        let is_parse_error = |result: Result<Module, Error>| match result {
+            Err(Error::Parse(_)) => true,
+            _ => false,
+        };
+        assert!(is_parse_error(context.from_bitcode_bytes(b"BC\xc0\xde not bitcode")));
+        assert!(is_parse_error(context.from_bitcode_bytes_lazy(b"not bitcode at all")));
+    }
+
+    #[test]
+    fn clone_module_has_counterparts() {
+        let context = Context::new();
+        let module = context.parse_ir_str("@counter = global i32 0").unwrap();
+        module.link_in(context.parse_ir_str(ADD).unwrap()).unwrap();
+        let copy = module.clone_module();
+        let add = module.get_function("add").unwrap().counterpart_in(&copy).unwrap();
+        assert_eq!(add.get_name().as_deref(), Some("add"));
+        assert!(add.inner() != module.get_function("add").unwrap().inner());
+        assert!(module.get_global("counter").unwrap().counterpart_in(&copy).is_some());
+        drop(module);
+        copy.verify().unwrap();
+    }
+
+    #[test]
+    fn unescape_hex_escapes() {
+        assert_eq!(unescape_ir_string("plain.c"), "plain.c");
+        assert_eq!(unescape_ir_string("a\\22b\\5Cc"), "a\"b\\c");
+        assert_eq!(unescape_ir_string("tab\\09"), "tab\t");
+        assert_eq!(unescape_ir_string("\\C3\\A9t\\C3\\A9"), "été");
+    }
+
+    #[test]
+    fn unescape_leaves_invalid_escapes() {
+        assert_eq!(unescape_ir_string("\\zz"), "\\zz");
+        assert_eq!(unescape_ir_string("end\\4"), "end\\4");
+        assert_eq!(unescape_ir_string("\\"), "\\");
+    }
+
+    #[test]
+    fn ir_string_after_prefix() {
+        assert_eq!(ir_string_after("source_filename = \"main\\22.c\"", "source_filename = "), Some("main\".c".to_owned()));
+        assert_eq!(ir_string_after("module asm \"nop\"  ", "module asm "), Some("nop".to_owned()));
+        assert_eq!(ir_string_after("module asm \"", "module asm "), None);
+        assert_eq!(ir_string_after("; ModuleID = 'main'", "source_filename = "), None);
+    }
+
+    #[test]
+    fn delete_only_unused_values() {
+        let context = Context::new();
+        let mut module = context.parse_ir_str("
+            @counter = global i32 0
+            @unused = global i32 0
+
+            define i32 @get() {
+            entry:
+              %value = load i32, i32* @counter
+              br label %exit
+            dead:
+              ret i32 0
+            exit:
+              ret i32 %value
+            }
+
+            define i32 @main() {
+              %value = call i32 @get()
+              ret i32 %value
+            }
+        ").unwrap();
+        assert!(module.delete_function("get").is_err());
+        assert!(module.delete_global("counter").is_err());
+        assert!(module.delete_block("get", "entry").is_err());
+        assert!(module.delete_block("get", "exit").is_err());
+        assert_eq!(module.delete_block("get", "dead").unwrap(), true);
+        assert_eq!(module.delete_block("get", "dead").unwrap(), false);
+        assert_eq!(module.delete_global("unused").unwrap(), true);
+        assert_eq!(module.delete_function("main").unwrap(), true);
+        assert_eq!(module.delete_function("get").unwrap(), true);
+        assert_eq!(module.delete_function("missing").unwrap(), false);
+        module.verify().unwrap();
+    }
 }
\ No newline at end of file
@@ -0,0 +1,187 @@
+//! Provides a wrapper for the ORC JIT, which compiles modules incrementally
+use super::*;
+use super::c_api::*;
+
+use engine::{JitFunction, JitSignature};
+use libc::c_void;
+use llvm_sys::orc::*;
+use llvm_sys::support::LLVMSearchForAddressOfSymbol;
+use std::ffi::CStr;
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr::null_mut;
+use target::TargetMachine;
+
+/// A handle to a module added to an `OrcJit`
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub struct ModuleHandle(LLVMOrcModuleHandle);
+
+/// A function that resolves the address of a symbol used by JIT compiled code
+type Resolver = Box<dyn FnMut(&str) -> Option<u64>>;
+
+/// A wrapper around a `LLVMOrcJITStackRef`
+///
+/// Modules can be added and removed one at a time. Symbols used by a module are first looked up
+/// among the modules already added to the JIT, then passed to the resolver if one is set, and
+/// finally searched for in the running program and loaded libraries.
+pub struct OrcJit<'ctx> {
+    stack: LLVMOrcJITStackRef,
+    resolver: *mut Option<Resolver>,
+    marker: PhantomData<&'ctx Context>,
+}
+
+impl<'ctx> OrcJit<'ctx> {
+    /// Creates a JIT that compiles code with the given target machine
+    ///
+    /// The symbols of the running program are loaded so that modules can use them.
+    pub fn new(machine: TargetMachine) -> Result<OrcJit<'ctx>, Error> {
+        target::initialize_asm_printers()?;
+        engine::load_library_permanently(None)?;
+        Ok(OrcJit {
+            stack: unsafe {
+                LLVMOrcCreateInstance(machine.into_inner()?)
+            },
+            resolver: Box::into_raw(Box::new(None)),
+            marker: PhantomData,
+        })
+    }
+
+    /// Sets the function used to resolve symbols, replacing any previous one
+    ///
+    /// There is a single resolver shared by every module, so it is also used for modules that
+    /// were added before this call but still have symbols to resolve, such as lazy modules.
+    pub fn set_resolver<R>(&mut self, resolver: R) where R: FnMut(&str) -> Option<u64> + 'static {
+        unsafe {
+            *self.resolver = Some(Box::new(resolver));
+        }
+    }
+
+    /// Adds a module that is compiled immediately
+    pub fn add_module(&self, module: Module<'ctx>) -> Result<ModuleHandle, Error> {
+        self.add(module, LLVMOrcAddEagerlyCompiledIR)
+    }
+
+    /// Adds a module whose functions are compiled the first time they are called
+    pub fn add_lazy_module(&self, module: Module<'ctx>) -> Result<ModuleHandle, Error> {
+        self.add(module, LLVMOrcAddLazilyCompiledIR)
+    }
+
+    /// Adds a module with one of the ORC functions
+    fn add(&self, mut module: Module<'ctx>, add: unsafe extern "C" fn(
+        LLVMOrcJITStackRef,
+        *mut LLVMOrcModuleHandle,
+        LLVMSharedModuleRef,
+        LLVMOrcSymbolResolverFn,
+        *mut c_void,
+    ) -> LLVMOrcErrorCode) -> Result<ModuleHandle, Error> {
+        unsafe {
+            let shared = LLVMOrcMakeSharedModule(module.module.take().unwrap());
+            let mut handle = 0;
+            let code = add(
+                self.stack,
+                &mut handle,
+                shared,
+                resolve,
+                self.resolver as *mut c_void,
+            );
+            LLVMOrcDisposeSharedModuleRef(shared);
+            self.check(code)?;
+            Ok(ModuleHandle(handle))
+        }
+    }
+
+    /// Removes a module from the JIT, freeing its compiled code
+    ///
+    /// This requires a mutable borrow, so no function returned by `get_function` can still be used.
+    pub fn remove_module(&mut self, handle: ModuleHandle) -> Result<(), Error> {
+        unsafe {
+            self.check(LLVMOrcRemoveModule(self.stack, handle.0))
+        }
+    }
+
+    /// Returns the mangled name of a symbol
+    pub fn mangle<S>(&self, name: S) -> Result<String, Error> where S: AsRef<str> {
+        let name = into_c(name)?;
+        unsafe {
+            let mut mangled = null_mut();
+            LLVMOrcGetMangledSymbol(self.stack, &mut mangled, name.as_ptr());
+            let string = from_c(mangled).unwrap_or(String::new());
+            LLVMOrcDisposeMangledSymbol(mangled);
+            Ok(string)
+        }
+    }
+
+    /// Returns the address of a symbol, which must already be mangled
+    pub fn get_symbol_address<S>(&self, mangled: S) -> Result<u64, Error> where S: AsRef<str> {
+        let mangled = mangled.as_ref();
+        unsafe {
+            let mut address = 0;
+            self.check(LLVMOrcGetSymbolAddress(self.stack, &mut address, into_c(mangled)?.as_ptr()))?;
+            if address == 0 {
+                Err(Error::Engine(format!("symbol {} was not found", mangled)))
+            } else {
+                Ok(address)
+            }
+        }
+    }
+
+    /// Returns a pointer to a function with the given unmangled name
+    ///
+    /// # Safety
+    ///
+    /// The modules have been handed to the JIT, so the signature of the function can't be checked
+    /// and must match `F`.
+    pub unsafe fn get_function<F>(&self, name: &str) -> Result<JitFunction<'_, F>, Error> where F: JitSignature {
+        let address = self.get_symbol_address(self.mangle(name)?)?;
+        Ok(JitFunction {
+            address: address as usize,
+            marker: PhantomData,
+        })
+    }
+
+    /// Converts an error code into a `Result`
+    fn check(&self, code: LLVMOrcErrorCode) -> Result<(), Error> {
+        match code {
+            LLVMOrcErrorCode::LLVMOrcErrSuccess => Ok(()),
+            _ => Err(Error::Engine(unsafe {
+                from_c(LLVMOrcGetErrorMsg(self.stack)).unwrap_or(String::new())
+            })),
+        }
+    }
+
+    /// Returns the internal JIT stack reference
+    pub fn inner(&self) -> LLVMOrcJITStackRef {
+        self.stack
+    }
+}
+
+impl<'ctx> Drop for OrcJit<'ctx> {
+    fn drop(&mut self) {
+        unsafe {
+            LLVMOrcDisposeInstance(self.stack);
+            drop(Box::from_raw(self.resolver));
+        }
+    }
+}
+
+impl<'ctx> Debug for OrcJit<'ctx> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "OrcJit")
+    }
+}
+
+/// Resolves a symbol that wasn't found among the modules added to the JIT
+extern "C" fn resolve(name: *const i8, resolver: *mut c_void) -> u64 {
+    let resolved = panic::catch_unwind(AssertUnwindSafe(|| unsafe {
+        let resolver = &mut *(resolver as *mut Option<Resolver>);
+        match (resolver.as_mut(), CStr::from_ptr(name).to_str()) {
+            (Some(resolver), Ok(name)) => resolver(name),
+            _ => None,
+        }
+    }));
+    match resolved {
+        Ok(Some(address)) => address,
+        _ => unsafe {
+            LLVMSearchForAddressOfSymbol(name) as u64
+        },
+    }
+}
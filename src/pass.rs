@@ -0,0 +1,531 @@
+//! Provides wrappers for running optimization passes on modules and functions
+
+use super::*;
+
+use llvm_sys::transforms::ipo::*;
+use llvm_sys::transforms::pass_manager_builder::*;
+use llvm_sys::transforms::scalar::*;
+use llvm_sys::transforms::vectorize::*;
+use target::OptLevel;
+
+/// An optimization pass provided by LLVM
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Pass {
+    /// Aggressive dead code elimination (`adce`)
+    AggressiveDce,
+    /// Dead code elimination based on tracking used bits (`bdce`)
+    BitTrackingDce,
+    /// Uses assumptions to improve the alignment of loads and stores (`alignment-from-assumptions`)
+    AlignmentFromAssumptions,
+    /// Simplifies the control flow graph (`simplifycfg`)
+    CfgSimplification,
+    /// Dead store elimination (`dse`)
+    DeadStoreElimination,
+    /// Scalarizes vector operations (`scalarizer`)
+    Scalarizer,
+    /// Merges loads and stores in diamonds (`mldst-motion`)
+    MergedLoadStoreMotion,
+    /// Global value numbering (`gvn`)
+    Gvn,
+    /// Canonicalizes induction variables (`indvars`)
+    IndVarSimplify,
+    /// Combines redundant instructions (`instcombine`)
+    InstructionCombining,
+    /// Threads jumps through blocks with known conditions (`jump-threading`)
+    JumpThreading,
+    /// Loop invariant code motion (`licm`)
+    Licm,
+    /// Deletes dead loops (`loop-deletion`)
+    LoopDeletion,
+    /// Recognizes loop idioms like `memset` (`loop-idiom`)
+    LoopIdiom,
+    /// Rotates loops (`loop-rotate`)
+    LoopRotate,
+    /// Rerolls unrolled loops (`loop-reroll`)
+    LoopReroll,
+    /// Unrolls loops (`loop-unroll`)
+    LoopUnroll,
+    /// Unswitches loops (`loop-unswitch`)
+    LoopUnswitch,
+    /// Optimizes uses of `memcpy` (`memcpyopt`)
+    MemCpyOpt,
+    /// Partially inlines calls to library functions (`partially-inline-libcalls`)
+    PartiallyInlineLibCalls,
+    /// Lowers switch instructions to branches (`lowerswitch`)
+    LowerSwitch,
+    /// Promotes memory to registers (`mem2reg`)
+    PromoteMemoryToRegister,
+    /// Reassociates expressions (`reassociate`)
+    Reassociate,
+    /// Sparse conditional constant propagation (`sccp`)
+    Sccp,
+    /// Scalar replacement of aggregates (`sroa`)
+    ScalarReplAggregates,
+    /// Simplifies calls to library functions (`simplify-libcalls`)
+    SimplifyLibCalls,
+    /// Tail call elimination (`tailcallelim`)
+    TailCallElimination,
+    /// Simple constant propagation (`constprop`)
+    ConstantPropagation,
+    /// Demotes registers to memory (`reg2mem`)
+    DemoteMemoryToRegister,
+    /// Verifies the IR (`verify`)
+    Verifier,
+    /// Propagates values known from conditions (`correlated-propagation`)
+    CorrelatedValuePropagation,
+    /// Early common subexpression elimination (`early-cse`)
+    EarlyCse,
+    /// Lowers `llvm.expect` intrinsics (`lower-expect`)
+    LowerExpectIntrinsic,
+    /// Type based alias analysis (`tbaa`)
+    TypeBasedAliasAnalysis,
+    /// Scoped no-alias alias analysis (`scoped-noalias`)
+    ScopedNoAliasAa,
+    /// Basic alias analysis (`basicaa`)
+    BasicAliasAnalysis,
+    /// Promotes pointer arguments to values (`argpromotion`)
+    ArgumentPromotion,
+    /// Merges duplicate global constants (`constmerge`)
+    ConstantMerge,
+    /// Removes dead arguments (`deadargelim`)
+    DeadArgElimination,
+    /// Infers function attributes (`functionattrs`)
+    FunctionAttrs,
+    /// Inlines functions (`inline`)
+    FunctionInlining,
+    /// Inlines functions marked `alwaysinline` (`always-inline`)
+    AlwaysInliner,
+    /// Removes unused globals (`globaldce`)
+    GlobalDce,
+    /// Optimizes globals (`globalopt`)
+    GlobalOptimizer,
+    /// Interprocedural constant propagation (`ipconstprop`)
+    IpConstantPropagation,
+    /// Removes unused exception handling information (`prune-eh`)
+    PruneEh,
+    /// Interprocedural sparse conditional constant propagation (`ipsccp`)
+    IpSccp,
+    /// Makes globals internal, except `main` if `all_but_main` is set (`internalize`)
+    Internalize {
+        /// Whether `main` should be kept external
+        all_but_main: bool,
+    },
+    /// Removes unused function declarations (`strip-dead-prototypes`)
+    StripDeadPrototypes,
+    /// Removes symbol names (`strip`)
+    StripSymbols,
+    /// Vectorizes loops (`loop-vectorize`)
+    LoopVectorize,
+    /// Vectorizes straight line code (`slp-vectorizer`)
+    SlpVectorize,
+}
+
+impl Pass {
+    /// Returns true if this pass can be run on a single function
+    ///
+    /// Interprocedural passes can only be added to a `PassManager`.
+    pub fn is_function_pass(&self) -> bool {
+        use self::Pass::*;
+        match self {
+            &ArgumentPromotion | &ConstantMerge | &DeadArgElimination | &FunctionAttrs |
+            &FunctionInlining | &AlwaysInliner | &GlobalDce | &GlobalOptimizer |
+            &IpConstantPropagation | &PruneEh | &IpSccp | &Internalize { .. } |
+            &StripDeadPrototypes | &StripSymbols => false,
+            _ => true,
+        }
+    }
+
+    /// Adds this pass to a `LLVMPassManagerRef`
+    ///
+    /// # Safety
+    ///
+    /// The pass manager must be valid, and must be a function pass manager only if
+    /// `is_function_pass` returns true.
+    pub unsafe fn add_to(&self, manager: LLVMPassManagerRef) {
+        use self::Pass::*;
+        match self {
+            &AggressiveDce => LLVMAddAggressiveDCEPass(manager),
+            &BitTrackingDce => LLVMAddBitTrackingDCEPass(manager),
+            &AlignmentFromAssumptions => LLVMAddAlignmentFromAssumptionsPass(manager),
+            &CfgSimplification => LLVMAddCFGSimplificationPass(manager),
+            &DeadStoreElimination => LLVMAddDeadStoreEliminationPass(manager),
+            &Scalarizer => LLVMAddScalarizerPass(manager),
+            &MergedLoadStoreMotion => LLVMAddMergedLoadStoreMotionPass(manager),
+            &Gvn => LLVMAddGVNPass(manager),
+            &IndVarSimplify => LLVMAddIndVarSimplifyPass(manager),
+            &InstructionCombining => LLVMAddInstructionCombiningPass(manager),
+            &JumpThreading => LLVMAddJumpThreadingPass(manager),
+            &Licm => LLVMAddLICMPass(manager),
+            &LoopDeletion => LLVMAddLoopDeletionPass(manager),
+            &LoopIdiom => LLVMAddLoopIdiomPass(manager),
+            &LoopRotate => LLVMAddLoopRotatePass(manager),
+            &LoopReroll => LLVMAddLoopRerollPass(manager),
+            &LoopUnroll => LLVMAddLoopUnrollPass(manager),
+            &LoopUnswitch => LLVMAddLoopUnswitchPass(manager),
+            &MemCpyOpt => LLVMAddMemCpyOptPass(manager),
+            &PartiallyInlineLibCalls => LLVMAddPartiallyInlineLibCallsPass(manager),
+            &LowerSwitch => LLVMAddLowerSwitchPass(manager),
+            &PromoteMemoryToRegister => LLVMAddPromoteMemoryToRegisterPass(manager),
+            &Reassociate => LLVMAddReassociatePass(manager),
+            &Sccp => LLVMAddSCCPPass(manager),
+            &ScalarReplAggregates => LLVMAddScalarReplAggregatesPass(manager),
+            &SimplifyLibCalls => LLVMAddSimplifyLibCallsPass(manager),
+            &TailCallElimination => LLVMAddTailCallEliminationPass(manager),
+            &ConstantPropagation => LLVMAddConstantPropagationPass(manager),
+            &DemoteMemoryToRegister => LLVMAddDemoteMemoryToRegisterPass(manager),
+            &Verifier => LLVMAddVerifierPass(manager),
+            &CorrelatedValuePropagation => LLVMAddCorrelatedValuePropagationPass(manager),
+            &EarlyCse => LLVMAddEarlyCSEPass(manager),
+            &LowerExpectIntrinsic => LLVMAddLowerExpectIntrinsicPass(manager),
+            &TypeBasedAliasAnalysis => LLVMAddTypeBasedAliasAnalysisPass(manager),
+            &ScopedNoAliasAa => LLVMAddScopedNoAliasAAPass(manager),
+            &BasicAliasAnalysis => LLVMAddBasicAliasAnalysisPass(manager),
+            &ArgumentPromotion => LLVMAddArgumentPromotionPass(manager),
+            &ConstantMerge => LLVMAddConstantMergePass(manager),
+            &DeadArgElimination => LLVMAddDeadArgEliminationPass(manager),
+            &FunctionAttrs => LLVMAddFunctionAttrsPass(manager),
+            &FunctionInlining => LLVMAddFunctionInliningPass(manager),
+            &AlwaysInliner => LLVMAddAlwaysInlinerPass(manager),
+            &GlobalDce => LLVMAddGlobalDCEPass(manager),
+            &GlobalOptimizer => LLVMAddGlobalOptimizerPass(manager),
+            &IpConstantPropagation => LLVMAddIPConstantPropagationPass(manager),
+            &PruneEh => LLVMAddPruneEHPass(manager),
+            &IpSccp => LLVMAddIPSCCPPass(manager),
+            &Internalize { all_but_main } => LLVMAddInternalizePass(manager, all_but_main as u32),
+            &StripDeadPrototypes => LLVMAddStripDeadPrototypesPass(manager),
+            &StripSymbols => LLVMAddStripSymbolsPass(manager),
+            &LoopVectorize => LLVMAddLoopVectorizePass(manager),
+            &SlpVectorize => LLVMAddSLPVectorizePass(manager),
+        }
+    }
+}
+
+/// A wrapper around a `LLVMPassManagerRef` that runs passes on whole modules
+pub struct PassManager {
+    manager: LLVMPassManagerRef,
+}
+
+impl PassManager {
+    /// Creates an empty pass manager
+    pub fn new() -> PassManager {
+        PassManager {
+            manager: unsafe {
+                LLVMCreatePassManager()
+            },
+        }
+    }
+
+    /// Creates a pass manager with the given passes
+    pub fn with_passes(passes: &[Pass]) -> PassManager {
+        let manager = PassManager::new();
+        for &pass in passes {
+            manager.add(pass);
+        }
+        manager
+    }
+
+    /// Adds a pass to the end of the pass manager
+    pub fn add(&self, pass: Pass) -> &PassManager {
+        unsafe {
+            pass.add_to(self.manager);
+        }
+        self
+    }
+
+    /// Runs the passes on a module, returning true if it was changed
+    ///
+    /// This requires a mutable borrow, since passes may delete any value in the module.
+    pub fn run(&self, module: &mut Module) -> bool {
+        unsafe {
+            LLVMRunPassManager(self.manager, module.inner()) != 0
+        }
+    }
+
+    /// Returns the internal pass manager reference
+    pub fn inner(&self) -> LLVMPassManagerRef {
+        self.manager
+    }
+}
+
+impl Default for PassManager {
+    fn default() -> PassManager {
+        PassManager::new()
+    }
+}
+
+impl Drop for PassManager {
+    fn drop(&mut self) {
+        unsafe {
+            LLVMDisposePassManager(self.manager);
+        }
+    }
+}
+
+impl Debug for PassManager {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "PassManager")
+    }
+}
+
+/// A wrapper around a `LLVMPassManagerRef` that runs passes on the functions of a module
+///
+/// The module is mutably borrowed while the pass manager exists, since passes may delete any
+/// instruction or block in the functions they run on.
+pub struct FunctionPassManager<'m, 'ctx: 'm> {
+    manager: LLVMPassManagerRef,
+    module: &'m mut Module<'ctx>,
+}
+
+impl<'m, 'ctx> FunctionPassManager<'m, 'ctx> {
+    /// Creates an empty pass manager for the functions of a module
+    pub fn new(module: &'m mut Module<'ctx>) -> FunctionPassManager<'m, 'ctx> {
+        FunctionPassManager {
+            manager: unsafe {
+                LLVMCreateFunctionPassManagerForModule(module.inner())
+            },
+            module,
+        }
+    }
+
+    /// Adds a pass to the end of the pass manager
+    ///
+    /// Returns an error if the pass can't be run on a single function.
+    pub fn add(&self, pass: Pass) -> Result<&FunctionPassManager<'m, 'ctx>, Error> {
+        if !pass.is_function_pass() {
+            return Err(Error::Pass(format!("{:?} cannot be run on a single function", pass)));
+        }
+        unsafe {
+            pass.add_to(self.manager);
+        }
+        Ok(self)
+    }
+
+    /// Runs the passes on the function with the given name, returning true if it was changed
+    pub fn run<S>(&self, function: S) -> Result<bool, Error> where S: AsRef<str> {
+        let name = function.as_ref();
+        let function = match self.module.get_function(name) {
+            Some(function) => function.value,
+            None => return Err(Error::Pass(format!("function {} was not found", name))),
+        };
+        unsafe {
+            LLVMInitializeFunctionPassManager(self.manager);
+            let changed = LLVMRunFunctionPassManager(self.manager, function) != 0;
+            LLVMFinalizeFunctionPassManager(self.manager);
+            Ok(changed)
+        }
+    }
+
+    /// Runs the passes on every function defined in the module, returning true if any changed
+    pub fn run_all(&self) -> bool {
+        let mut changed = false;
+        unsafe {
+            changed |= LLVMInitializeFunctionPassManager(self.manager) != 0;
+            for function in self.module.functions() {
+                if LLVMIsDeclaration(function.value) == 0 {
+                    changed |= LLVMRunFunctionPassManager(self.manager, function.value) != 0;
+                }
+            }
+            changed |= LLVMFinalizeFunctionPassManager(self.manager) != 0;
+        }
+        changed
+    }
+
+    /// Returns the internal pass manager reference
+    pub fn inner(&self) -> LLVMPassManagerRef {
+        self.manager
+    }
+}
+
+impl<'m, 'ctx> Drop for FunctionPassManager<'m, 'ctx> {
+    fn drop(&mut self) {
+        unsafe {
+            LLVMDisposePassManager(self.manager);
+        }
+    }
+}
+
+impl<'m, 'ctx> Debug for FunctionPassManager<'m, 'ctx> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "FunctionPassManager")
+    }
+}
+
+/// How much the optimizer should prefer smaller code over faster code
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum SizeLevel {
+    /// Don't optimize for size
+    None = 0,
+    /// Optimize for size (`-Os`)
+    Small = 1,
+    /// Optimize aggressively for size (`-Oz`)
+    Tiny = 2,
+}
+
+/// A wrapper around a `LLVMPassManagerBuilderRef` that adds the standard optimization pipeline
+///
+/// The defaults mirror those used by `clang` for the given optimization and size levels. The C API
+/// of LLVM 5 cannot enable the vectorizers inside the pipeline, so like the extension points used
+/// by `clang`, they are added after the standard module passes instead.
+pub struct PassManagerBuilder {
+    builder: LLVMPassManagerBuilderRef,
+    level: OptLevel,
+    inliner_threshold: Option<u32>,
+    loop_vectorize: bool,
+    slp_vectorize: bool,
+}
+
+impl PassManagerBuilder {
+    /// Creates a builder for the given optimization and size levels
+    pub fn new(level: OptLevel, size: SizeLevel) -> PassManagerBuilder {
+        let optimize = level as u32 > 1;
+        let mut builder = PassManagerBuilder {
+            builder: unsafe {
+                LLVMPassManagerBuilderCreate()
+            },
+            level,
+            inliner_threshold: None,
+            loop_vectorize: optimize && size != SizeLevel::Tiny,
+            slp_vectorize: optimize,
+        };
+        unsafe {
+            LLVMPassManagerBuilderSetOptLevel(builder.builder, level as u32);
+            LLVMPassManagerBuilderSetSizeLevel(builder.builder, size as u32);
+        }
+        builder.set_unroll_loops(optimize);
+        if optimize {
+            builder.set_inliner_threshold(match (level, size) {
+                (_, SizeLevel::Tiny) => 25,
+                (_, SizeLevel::Small) => 75,
+                (OptLevel::Aggressive, _) => 250,
+                _ => 225,
+            });
+        }
+        builder
+    }
+
+    /// Creates a builder for `-O0`
+    pub fn o0() -> PassManagerBuilder {
+        PassManagerBuilder::new(OptLevel::None, SizeLevel::None)
+    }
+
+    /// Creates a builder for `-O1`
+    pub fn o1() -> PassManagerBuilder {
+        PassManagerBuilder::new(OptLevel::Less, SizeLevel::None)
+    }
+
+    /// Creates a builder for `-O2`
+    pub fn o2() -> PassManagerBuilder {
+        PassManagerBuilder::new(OptLevel::Default, SizeLevel::None)
+    }
+
+    /// Creates a builder for `-O3`
+    pub fn o3() -> PassManagerBuilder {
+        PassManagerBuilder::new(OptLevel::Aggressive, SizeLevel::None)
+    }
+
+    /// Creates a builder for `-Os`
+    pub fn os() -> PassManagerBuilder {
+        PassManagerBuilder::new(OptLevel::Default, SizeLevel::Small)
+    }
+
+    /// Creates a builder for `-Oz`
+    pub fn oz() -> PassManagerBuilder {
+        PassManagerBuilder::new(OptLevel::Default, SizeLevel::Tiny)
+    }
+
+    /// Inline functions whose cost is below the given threshold
+    ///
+    /// If no threshold is set, only functions marked `alwaysinline` are inlined.
+    pub fn set_inliner_threshold(&mut self, threshold: u32) -> &mut PassManagerBuilder {
+        self.inliner_threshold = Some(threshold);
+        self
+    }
+
+    /// Set whether loops should be unrolled
+    pub fn set_unroll_loops(&mut self, unroll: bool) -> &mut PassManagerBuilder {
+        unsafe {
+            LLVMPassManagerBuilderSetDisableUnrollLoops(self.builder, !unroll as LLVMBool);
+        }
+        self
+    }
+
+    /// Set whether library calls should be simplified
+    pub fn set_simplify_lib_calls(&mut self, simplify: bool) -> &mut PassManagerBuilder {
+        unsafe {
+            LLVMPassManagerBuilderSetDisableSimplifyLibCalls(self.builder, !simplify as LLVMBool);
+        }
+        self
+    }
+
+    /// Set whether loops should be vectorized
+    pub fn set_loop_vectorize(&mut self, vectorize: bool) -> &mut PassManagerBuilder {
+        self.loop_vectorize = vectorize;
+        self
+    }
+
+    /// Set whether straight line code should be vectorized
+    pub fn set_slp_vectorize(&mut self, vectorize: bool) -> &mut PassManagerBuilder {
+        self.slp_vectorize = vectorize;
+        self
+    }
+
+    /// Adds the standard module passes to a pass manager, followed by the enabled vectorizers
+    pub fn populate_module(&self, manager: &PassManager) {
+        unsafe {
+            match self.inliner_threshold {
+                // The pass manager takes the inliner, so a new one is needed every time
+                Some(threshold) => LLVMPassManagerBuilderUseInlinerWithThreshold(self.builder, threshold),
+                None => Pass::AlwaysInliner.add_to(manager.inner()),
+            }
+            LLVMPassManagerBuilderPopulateModulePassManager(self.builder, manager.inner());
+        }
+        if self.loop_vectorize {
+            manager.add(Pass::LoopVectorize);
+        }
+        if self.slp_vectorize {
+            manager.add(Pass::SlpVectorize);
+        }
+    }
+
+    /// Adds the standard function passes to a pass manager
+    pub fn populate_function(&self, manager: &FunctionPassManager) {
+        unsafe {
+            LLVMPassManagerBuilderPopulateFunctionPassManager(self.builder, manager.inner());
+        }
+    }
+
+    /// Adds the standard link time optimization passes to a pass manager
+    pub fn populate_lto(&self, manager: &PassManager, internalize: bool, run_inliner: bool) {
+        unsafe {
+            // The inliner is only taken when the full link time pipeline runs it
+            if let (Some(threshold), true) = (self.inliner_threshold, run_inliner && self.level as u32 > 1) {
+                LLVMPassManagerBuilderUseInlinerWithThreshold(self.builder, threshold);
+            }
+            LLVMPassManagerBuilderPopulateLTOPassManager(
+                self.builder,
+                manager.inner(),
+                internalize as LLVMBool,
+                run_inliner as LLVMBool,
+            );
+        }
+    }
+
+    /// Returns the internal pass manager builder reference
+    pub fn inner(&self) -> LLVMPassManagerBuilderRef {
+        self.builder
+    }
+}
+
+impl Drop for PassManagerBuilder {
+    fn drop(&mut self) {
+        unsafe {
+            LLVMPassManagerBuilderDispose(self.builder);
+        }
+    }
+}
+
+impl Debug for PassManagerBuilder {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "PassManagerBuilder")
+    }
+}
\ No newline at end of file
@@ -2,27 +2,511 @@
 use super::*;
 use super::c_api::*;
 
+use buffer::MemoryBuffer;
+use std::collections::BTreeMap;
+use std::mem;
+use std::panic;
 use std::path::Path;
 use std::ptr::null_mut;
+use std::rc::Rc;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex, Once};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
 use llvm_sys::target::*;
 use llvm_sys::target_machine::*;
 
-static mut UNINITIALIZED: bool = true;
+static INITIALIZE_TARGETS: Once = Once::new();
+static INITIALIZE_ASM_PRINTERS: Once = Once::new();
+static TARGETS_FAILED: AtomicBool = AtomicBool::new(false);
+static ASM_PRINTERS_FAILED: AtomicBool = AtomicBool::new(false);
 
-unsafe fn initialize() {
-    if UNINITIALIZED {
-        UNINITIALIZED = false;
-        LLVM_InitializeAllTargets();
-        LLVM_InitializeAllTargetInfos();
-        LLVM_InitializeAllTargetMCs();
+/// Declares the initializers of each target that can be selected with a cargo feature
+///
+/// The first list initializes the target itself and the second its assembly printer and parser.
+macro_rules! selected_targets {
+    ($($feature:tt => [$($target:ident),*], [$($asm:ident),*];)*) => {
+        #[cfg(not(feature = "all-targets"))]
+        extern "C" {
+            $(
+                $(#[cfg(feature = $feature)] fn $target();)*
+                $(#[cfg(feature = $feature)] fn $asm();)*
+            )*
+        }
+
+        #[cfg(not(feature = "all-targets"))]
+        unsafe fn initialize_targets() -> bool {
+            $(#[cfg(feature = $feature)] { $($target();)* })*
+            #[cfg(feature = "native")] {
+                if LLVM_InitializeNativeTarget() != 0 {
+                    return false;
+                }
+            }
+            true
+        }
+
+        #[cfg(not(feature = "all-targets"))]
+        unsafe fn initialize_asm() -> bool {
+            $(#[cfg(feature = $feature)] { $($asm();)* })*
+            #[cfg(feature = "native")] {
+                if LLVM_InitializeNativeAsmPrinter() != 0 || LLVM_InitializeNativeAsmParser() != 0 {
+                    return false;
+                }
+            }
+            true
+        }
+    }
+}
+
+selected_targets! {
+    "x86" => [LLVMInitializeX86TargetInfo, LLVMInitializeX86Target, LLVMInitializeX86TargetMC],
+        [LLVMInitializeX86AsmPrinter, LLVMInitializeX86AsmParser];
+    "aarch64" => [LLVMInitializeAArch64TargetInfo, LLVMInitializeAArch64Target, LLVMInitializeAArch64TargetMC],
+        [LLVMInitializeAArch64AsmPrinter, LLVMInitializeAArch64AsmParser];
+    "arm" => [LLVMInitializeARMTargetInfo, LLVMInitializeARMTarget, LLVMInitializeARMTargetMC],
+        [LLVMInitializeARMAsmPrinter, LLVMInitializeARMAsmParser];
+    "mips" => [LLVMInitializeMipsTargetInfo, LLVMInitializeMipsTarget, LLVMInitializeMipsTargetMC],
+        [LLVMInitializeMipsAsmPrinter, LLVMInitializeMipsAsmParser];
+    "powerpc" => [LLVMInitializePowerPCTargetInfo, LLVMInitializePowerPCTarget, LLVMInitializePowerPCTargetMC],
+        [LLVMInitializePowerPCAsmPrinter, LLVMInitializePowerPCAsmParser];
+    "sparc" => [LLVMInitializeSparcTargetInfo, LLVMInitializeSparcTarget, LLVMInitializeSparcTargetMC],
+        [LLVMInitializeSparcAsmPrinter, LLVMInitializeSparcAsmParser];
+    "systemz" => [LLVMInitializeSystemZTargetInfo, LLVMInitializeSystemZTarget, LLVMInitializeSystemZTargetMC],
+        [LLVMInitializeSystemZAsmPrinter, LLVMInitializeSystemZAsmParser];
+    "amdgpu" => [LLVMInitializeAMDGPUTargetInfo, LLVMInitializeAMDGPUTarget, LLVMInitializeAMDGPUTargetMC],
+        [LLVMInitializeAMDGPUAsmPrinter, LLVMInitializeAMDGPUAsmParser];
+    "hexagon" => [LLVMInitializeHexagonTargetInfo, LLVMInitializeHexagonTarget, LLVMInitializeHexagonTargetMC],
+        [LLVMInitializeHexagonAsmPrinter, LLVMInitializeHexagonAsmParser];
+    "lanai" => [LLVMInitializeLanaiTargetInfo, LLVMInitializeLanaiTarget, LLVMInitializeLanaiTargetMC],
+        [LLVMInitializeLanaiAsmPrinter, LLVMInitializeLanaiAsmParser];
+    "nvptx" => [LLVMInitializeNVPTXTargetInfo, LLVMInitializeNVPTXTarget, LLVMInitializeNVPTXTargetMC],
+        [LLVMInitializeNVPTXAsmPrinter];
+    "bpf" => [LLVMInitializeBPFTargetInfo, LLVMInitializeBPFTarget, LLVMInitializeBPFTargetMC],
+        [LLVMInitializeBPFAsmPrinter];
+    "msp430" => [LLVMInitializeMSP430TargetInfo, LLVMInitializeMSP430Target, LLVMInitializeMSP430TargetMC],
+        [LLVMInitializeMSP430AsmPrinter];
+    "xcore" => [LLVMInitializeXCoreTargetInfo, LLVMInitializeXCoreTarget, LLVMInitializeXCoreTargetMC],
+        [LLVMInitializeXCoreAsmPrinter];
+}
+
+#[cfg(feature = "all-targets")]
+unsafe fn initialize_targets() -> bool {
+    LLVM_InitializeAllTargetInfos();
+    LLVM_InitializeAllTargets();
+    LLVM_InitializeAllTargetMCs();
+    true
+}
+
+#[cfg(feature = "all-targets")]
+unsafe fn initialize_asm() -> bool {
+    LLVM_InitializeAllAsmPrinters();
+    LLVM_InitializeAllAsmParsers();
+    true
+}
+
+/// Initializes the targets selected with cargo features
+///
+/// With the default `all-targets` feature every target compiled into LLVM is initialized.
+/// Otherwise only the targets enabled by their own features, such as `x86` or `aarch64`, and
+/// the host target if `native` is enabled, are initialized. Returns `Error::Target` if the host
+/// target isn't compiled into LLVM.
+pub(crate) fn initialize() -> Result<(), Error> {
+    INITIALIZE_TARGETS.call_once(|| unsafe {
+        if !initialize_targets() {
+            TARGETS_FAILED.store(true, Ordering::SeqCst);
+        }
+    });
+    if TARGETS_FAILED.load(Ordering::SeqCst) {
+        Err(Error::Target("the native target could not be initialized".to_owned()))
+    } else {
+        Ok(())
+    }
+}
+
+/// Initializes the assembly printers and parsers of the targets selected with cargo features
+pub(crate) fn initialize_asm_printers() -> Result<(), Error> {
+    initialize()?;
+    INITIALIZE_ASM_PRINTERS.call_once(|| unsafe {
+        if !initialize_asm() {
+            ASM_PRINTERS_FAILED.store(true, Ordering::SeqCst);
+        }
+    });
+    if ASM_PRINTERS_FAILED.load(Ordering::SeqCst) {
+        Err(Error::Target("the native assembly printer could not be initialized".to_owned()))
+    } else {
+        Ok(())
     }
 }
 
 /// The default target triple
-pub fn default_triple() -> String {
-    from_c(unsafe {
-        LLVMGetDefaultTargetTriple()
-    }).unwrap_or(String::new())
+pub fn default_triple() -> Triple {
+    Triple::normalized(&unsafe {
+        take_message(LLVMGetDefaultTargetTriple())
+    })
+}
+
+/// Builds and emits several modules in parallel, each with its own `Context`
+///
+/// Each job is a path paired with a function that builds the module to emit to that path. The jobs
+/// are shared between at most as many threads as the machine can run in parallel. A target machine
+/// with the given options is created on every thread, since LLVM modules and target machines
+/// cannot be shared between threads. Returns the first error that occurred, and if a job panics,
+/// the panic is resumed on the calling thread once the other threads have finished.
+pub fn emit_parallel<P, F>(jobs: Vec<(P, F)>, options: TargetMachineOptions,
+                           file_type: FileType) -> Result<(), Error>
+    where P: AsRef<Path> + Send + 'static,
+          F: for<'ctx> FnOnce(&'ctx Context) -> Result<Module<'ctx>, Error> + Send + 'static {
+    initialize_asm_printers()?;
+    let workers = thread::available_parallelism().map(|n| n.get()).unwrap_or(1).min(jobs.len());
+    let options = Arc::new(options);
+    let jobs = Arc::new(Mutex::new(jobs.into_iter()));
+    let handles: Vec<_> = (0..workers).map(|_| {
+        let options = options.clone();
+        let jobs = jobs.clone();
+        thread::spawn(move || -> Result<(), Error> {
+            let machine = TargetMachine::new_with_options(&options)?;
+            loop {
+                let job = jobs.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).next();
+                let (path, build) = match job {
+                    Some(job) => job,
+                    None => return Ok(()),
+                };
+                let context = Context::new();
+                let module = build(&context)?;
+                machine.emit_module_to_file(&module, path, file_type)?;
+            }
+        })
+    }).collect();
+    let mut result = Ok(());
+    let mut panicked = None;
+    for handle in handles {
+        match handle.join() {
+            Ok(emitted) => if result.is_ok() {
+                result = emitted;
+            },
+            Err(payload) => if panicked.is_none() {
+                panicked = Some(payload);
+            },
+        }
+    }
+    if let Some(payload) = panicked {
+        panic::resume_unwind(payload);
+    }
+    result
+}
+
+/// A target triple in the form `arch-vendor-os` or `arch-vendor-os-environment`
+///
+/// Triples are normalized when they are created, so missing components are filled in with
+/// `unknown` and `x86_64-linux-gnu` becomes `x86_64-unknown-linux-gnu`.
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+pub struct Triple {
+    triple: String,
+}
+
+/// Architectures that LLVM recognizes by their full name, with the width of a pointer in bits
+const ARCHITECTURES: &[(&str, u32)] = &[
+    ("i386", 32), ("i486", 32), ("i586", 32), ("i686", 32), ("i786", 32), ("i886", 32),
+    ("i986", 32), ("amd64", 64), ("x86_64", 64), ("x86_64h", 64), ("powerpc", 32), ("ppc", 32),
+    ("ppc32", 32), ("powerpc64", 64), ("ppu", 64), ("ppc64", 64), ("powerpc64le", 64),
+    ("ppc64le", 64), ("xscale", 32), ("xscaleeb", 32), ("arm64", 64), ("avr", 16),
+    ("msp430", 16), ("mips", 32), ("mipseb", 32), ("mipsallegrex", 32), ("mipsel", 32),
+    ("mipsallegrexel", 32), ("mips64", 64), ("mips64eb", 64), ("mips64el", 64), ("nios2", 32),
+    ("r600", 32), ("amdgcn", 64), ("riscv32", 32), ("riscv64", 64), ("hexagon", 32),
+    ("s390x", 64), ("systemz", 64), ("sparc", 32), ("sparcel", 32), ("sparcv9", 64),
+    ("sparc64", 64), ("tce", 32), ("tcele", 32), ("xcore", 32), ("nvptx", 32), ("nvptx64", 64),
+    ("le32", 32), ("le64", 64), ("amdil", 32), ("amdil64", 64), ("hsail", 32), ("hsail64", 64),
+    ("spir", 32), ("spir64", 64), ("lanai", 32), ("shave", 32), ("wasm32", 32), ("wasm64", 64),
+    ("renderscript32", 32), ("renderscript64", 64), ("bpf", 64), ("bpf_le", 64), ("bpfel", 64),
+    ("bpf_be", 64), ("bpfeb", 64),
+];
+
+/// Prefixes of architectures that LLVM recognizes with a sub-architecture or version, with the
+/// width of a pointer in bits
+const ARCHITECTURE_PREFIXES: &[(&str, u32)] = &[
+    ("arm", 32), ("thumb", 32), ("aarch64", 64), ("kalimba", 32),
+];
+
+/// Vendors that LLVM recognizes
+const VENDORS: &[&str] = &[
+    "apple", "pc", "scei", "bgp", "bgq", "fsl", "ibm", "img", "mti", "nvidia", "csr", "myriad",
+    "amd", "mesa", "suse", "oe",
+];
+
+/// Prefixes of operating systems that LLVM recognizes, apart from `mingw` and `cygwin`
+const OPERATING_SYSTEMS: &[&str] = &[
+    "cloudabi", "darwin", "dragonfly", "freebsd", "fuchsia", "ios", "kfreebsd", "linux", "lv2",
+    "macos", "netbsd", "openbsd", "solaris", "win32", "windows", "haiku", "minix", "rtems", "nacl",
+    "cnk", "bitrig", "aix", "cuda", "nvcl", "amdhsa", "ps4", "elfiamcu", "tvos", "watchos",
+    "mesa3d", "contiki",
+];
+
+/// Prefixes of environments that LLVM recognizes
+const ENVIRONMENTS: &[&str] = &[
+    "eabihf", "eabi", "gnuabi64", "gnueabihf", "gnueabi", "gnux32", "code16", "gnu", "android",
+    "musleabihf", "musleabi", "musl", "msvc", "itanium", "cygnus", "amdopencl", "coreclr",
+    "opencl",
+];
+
+/// Suffixes of object formats that LLVM recognizes in place of an environment
+const OBJECT_FORMATS: &[&str] = &["coff", "elf", "macho", "wasm"];
+
+impl Triple {
+    /// Parses and normalizes a triple, such as `x86_64-unknown-linux-gnu`
+    pub fn parse<S>(triple: S) -> Result<Triple, Error> where S: AsRef<str> {
+        let triple = triple.as_ref();
+        if triple.is_empty() || triple.starts_with('-') || !Triple::is_valid(triple) {
+            Err(Error::Target(format!("invalid target triple {:?}", triple)))
+        } else {
+            Ok(Triple::normalized(triple))
+        }
+    }
+
+    /// Builds a triple from its components
+    pub fn from_parts(arch: &str, vendor: &str, os: &str, environment: Option<&str>) -> Result<Triple, Error> {
+        let mut parts = vec![arch, vendor, os];
+        parts.extend(environment);
+        for part in &parts {
+            if part.is_empty() || part.contains('-') || !Triple::is_valid(part) {
+                return Err(Error::Target(format!("invalid target triple component {:?}", part)));
+            }
+        }
+        Ok(Triple::normalized(&parts.join("-")))
+    }
+
+    /// The triple of the host, as reported by LLVM
+    pub fn native() -> Triple {
+        default_triple()
+    }
+
+    /// Normalizes a triple without checking that it is valid
+    ///
+    /// This is a port of `Triple::normalize` from LLVM. Components that are recognized are moved
+    /// into their positions, and any that are missing are filled in with `unknown`.
+    pub(crate) fn normalized(triple: &str) -> Triple {
+        let mut parts: Vec<&str> = triple.split('-').collect();
+        let mut found = [false; 4];
+        for (pos, found) in found.iter_mut().enumerate() {
+            *found = parts.get(pos).map_or(false, |part| match pos {
+                2 => Triple::is_os(part),
+                _ => Triple::is_component(pos, part),
+            });
+        }
+        for pos in 0..4 {
+            if found[pos] {
+                continue;
+            }
+            let idx = match (0..parts.len()).find(|&idx| {
+                !(idx < 4 && found[idx]) && Triple::is_component(pos, parts[idx])
+            }) {
+                Some(idx) => idx,
+                None => continue,
+            };
+            if pos < idx {
+                // Insert the component to the left, pushing the components in the way to the right
+                let mut current = mem::take(&mut parts[idx]);
+                let mut i = pos;
+                while !current.is_empty() {
+                    while i < 4 && found[i] {
+                        i += 1;
+                    }
+                    mem::swap(&mut current, &mut parts[i]);
+                    i += 1;
+                }
+            } else if pos > idx {
+                // Insert empty components before it until it reaches its position
+                let mut idx = idx;
+                while idx < pos {
+                    let mut current = "";
+                    let mut i = idx;
+                    while i < parts.len() {
+                        mem::swap(&mut current, &mut parts[i]);
+                        if current.is_empty() {
+                            break;
+                        }
+                        i += 1;
+                        while i < 4 && found[i] {
+                            i += 1;
+                        }
+                    }
+                    if !current.is_empty() {
+                        parts.push(current);
+                    }
+                    idx += 1;
+                    while idx < 4 && found[idx] {
+                        idx += 1;
+                    }
+                }
+            }
+            found[pos] = true;
+        }
+
+        let mut parts: Vec<String> = parts.into_iter().map(|part| part.to_owned()).collect();
+        let vendor = parts.get(1).cloned().unwrap_or_default();
+        let os = parts.get(2).cloned().unwrap_or_default();
+        let environment = parts.get(3).cloned().unwrap_or_default();
+        let has_environment = ENVIRONMENTS.iter().any(|env| environment.starts_with(env));
+        let object_format = match parts.get(4) {
+            Some(part) => OBJECT_FORMATS.iter().find(|format| part.ends_with(*format)),
+            None if !has_environment => OBJECT_FORMATS.iter().find(|format| environment.ends_with(*format)),
+            None => None,
+        };
+        let is_win32 = os.starts_with("win32") || os.starts_with("windows");
+        let is_mingw = os.starts_with("mingw");
+        let is_cygwin = os.starts_with("cygwin");
+        if let Some(version) = environment.strip_prefix("androideabi") {
+            parts[3] = format!("android{}", version);
+        }
+        // SUSE uses `gnueabi` to mean `gnueabihf`
+        if vendor == "suse" && environment.starts_with("gnueabi") && !environment.starts_with("gnueabihf") {
+            parts[3] = "gnueabihf".to_owned();
+        }
+        if is_win32 {
+            parts.resize(4, String::new());
+            parts[2] = "windows".to_owned();
+            if !has_environment {
+                parts[3] = match object_format {
+                    None | Some(&"coff") => "msvc".to_owned(),
+                    Some(format) => format.to_string(),
+                };
+            }
+        } else if is_mingw || is_cygwin {
+            parts.resize(4, String::new());
+            parts[2] = "windows".to_owned();
+            parts[3] = if is_mingw { "gnu" } else { "cygnus" }.to_owned();
+        }
+        if is_mingw || is_cygwin || (is_win32 && has_environment) {
+            if let Some(format) = object_format.filter(|&&format| format != "coff") {
+                parts.resize(5, String::new());
+                parts[4] = format.to_string();
+            }
+        }
+
+        if parts.len() < 3 {
+            parts.resize(3, String::new());
+        }
+        for part in &mut parts {
+            if part.is_empty() {
+                *part = "unknown".to_owned();
+            }
+        }
+        Triple {
+            triple: parts.join("-"),
+        }
+    }
+
+    /// Returns true if LLVM recognizes the component for the given position of a triple
+    fn is_component(pos: usize, part: &str) -> bool {
+        match pos {
+            0 => Triple::arch_pointer_width(part).is_some(),
+            1 => VENDORS.contains(&part),
+            2 => Triple::is_os(part) || part.starts_with("mingw") || part.starts_with("cygwin"),
+            _ => ENVIRONMENTS.iter().any(|env| part.starts_with(env)) || Triple::is_object_format(part),
+        }
+    }
+
+    fn arch_pointer_width(arch: &str) -> Option<u32> {
+        ARCHITECTURES.iter()
+            .find(|&&(name, _)| name == arch)
+            .or_else(|| ARCHITECTURE_PREFIXES.iter().find(|&&(prefix, _)| arch.starts_with(prefix)))
+            .map(|&(_, width)| width)
+    }
+
+    fn is_os(part: &str) -> bool {
+        OPERATING_SYSTEMS.iter().any(|os| part.starts_with(os))
+    }
+
+    fn is_object_format(part: &str) -> bool {
+        !ENVIRONMENTS.iter().any(|env| part.starts_with(env)) && OBJECT_FORMATS.iter().any(|format| part.ends_with(format))
+    }
+
+    fn is_valid(part: &str) -> bool {
+        !part.contains(|c: char| c.is_whitespace() || c.is_control())
+    }
+
+    fn component(&self, index: usize) -> Option<&str> {
+        self.triple.splitn(4, '-').nth(index)
+    }
+
+    /// The architecture, such as `x86_64` or `armv7`
+    pub fn arch(&self) -> &str {
+        self.component(0).unwrap()
+    }
+
+    /// The vendor, such as `pc`, `apple` or `unknown`
+    pub fn vendor(&self) -> &str {
+        self.component(1).unwrap()
+    }
+
+    /// The operating system, such as `linux`, `windows` or `darwin`
+    pub fn os(&self) -> &str {
+        self.component(2).unwrap()
+    }
+
+    /// The environment or ABI, such as `gnu` or `msvc`, if there is one
+    pub fn environment(&self) -> Option<&str> {
+        self.component(3)
+    }
+
+    /// Returns this triple as a string
+    pub fn as_str(&self) -> &str {
+        &self.triple
+    }
+
+    /// Returns true if the operating system is Windows
+    pub fn is_windows(&self) -> bool {
+        self.os().starts_with("windows")
+    }
+
+    /// Returns true if the operating system is macOS
+    pub fn is_macos(&self) -> bool {
+        self.os().starts_with("darwin") || self.os().starts_with("macos")
+    }
+
+    /// Returns true if the operating system is Linux
+    pub fn is_linux(&self) -> bool {
+        self.os().starts_with("linux")
+    }
+
+    /// Returns true if the architecture is WebAssembly
+    pub fn is_wasm(&self) -> bool {
+        self.arch().starts_with("wasm")
+    }
+
+    /// The width of a pointer in bits, if the architecture is known
+    pub fn pointer_width(&self) -> Option<u32> {
+        Triple::arch_pointer_width(self.arch())
+    }
+
+    /// Returns true if pointers are 64 bits wide
+    pub fn is_64bit(&self) -> bool {
+        self.pointer_width() == Some(64)
+    }
+
+    /// Returns true if pointers are 32 bits wide
+    pub fn is_32bit(&self) -> bool {
+        self.pointer_width() == Some(32)
+    }
+}
+
+impl FromStr for Triple {
+    type Err = Error;
+
+    fn from_str(triple: &str) -> Result<Triple, Error> {
+        Triple::parse(triple)
+    }
+}
+
+impl AsRef<str> for Triple {
+    fn as_ref(&self) -> &str {
+        &self.triple
+    }
+}
+
+impl Display for Triple {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.triple)
+    }
 }
 
 /// A renamed `LLVMCodeGenOptLevel`
@@ -52,6 +536,143 @@ impl OptLevel {
     }
 }
 
+/// A renamed `LLVMRelocMode`
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum RelocMode {
+    /// The default relocation model for the target
+    Default,
+    /// Non-relocatable code
+    Static,
+    /// Position independent code, for shared libraries
+    Pic,
+    /// Code with relocatable external references only
+    DynamicNoPic,
+}
+
+impl RelocMode {
+    /// The `LLVMRelocMode` this value represents
+    pub fn inner(&self) -> LLVMRelocMode {
+        use llvm_sys::target_machine::LLVMRelocMode::*;
+        use self::RelocMode::*;
+        match self {
+            &Default => LLVMRelocDefault,
+            &Static => LLVMRelocStatic,
+            &Pic => LLVMRelocPIC,
+            &DynamicNoPic => LLVMRelocDynamicNoPic,
+        }
+    }
+}
+
+/// A renamed `LLVMCodeModel`
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum CodeModel {
+    /// The default code model for the target
+    Default,
+    /// The default code model for JIT compilation
+    JitDefault,
+    /// The small code model
+    Small,
+    /// The kernel code model
+    Kernel,
+    /// The medium code model
+    Medium,
+    /// The large code model
+    Large,
+}
+
+impl CodeModel {
+    /// The `LLVMCodeModel` this value represents
+    pub fn inner(&self) -> LLVMCodeModel {
+        use llvm_sys::target_machine::LLVMCodeModel::*;
+        use self::CodeModel::*;
+        match self {
+            &Default => LLVMCodeModelDefault,
+            &JitDefault => LLVMCodeModelJITDefault,
+            &Small => LLVMCodeModelSmall,
+            &Kernel => LLVMCodeModelKernel,
+            &Medium => LLVMCodeModelMedium,
+            &Large => LLVMCodeModelLarge,
+        }
+    }
+}
+
+/// The options used to create a `TargetMachine`
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct TargetMachineOptions {
+    /// The target triple
+    pub triple: Triple,
+    /// The target CPU
+    pub cpu: String,
+    /// The target features, such as `+sse4.2,-avx`
+    pub features: String,
+    /// The code generation optimization level
+    pub level: OptLevel,
+    /// The relocation model
+    pub reloc: RelocMode,
+    /// The code model
+    pub code_model: CodeModel,
+}
+
+impl TargetMachineOptions {
+    /// Creates the default options for the given target triple
+    pub fn new(triple: Triple) -> TargetMachineOptions {
+        TargetMachineOptions {
+            triple,
+            cpu: "generic".to_owned(),
+            features: String::new(),
+            level: OptLevel::Default,
+            reloc: RelocMode::Default,
+            code_model: CodeModel::Default,
+        }
+    }
+
+    /// Creates the default options for the native target triple
+    pub fn native() -> TargetMachineOptions {
+        TargetMachineOptions::new(default_triple())
+    }
+
+    /// Sets the target CPU
+    pub fn with_cpu(mut self, cpu: String) -> TargetMachineOptions {
+        self.cpu = cpu;
+        self
+    }
+
+    /// Sets the target features
+    pub fn with_features(mut self, features: String) -> TargetMachineOptions {
+        self.features = features;
+        self
+    }
+
+    /// Sets the code generation optimization level
+    pub fn with_opt_level(mut self, level: OptLevel) -> TargetMachineOptions {
+        self.level = level;
+        self
+    }
+
+    /// Sets the relocation model
+    pub fn with_reloc_mode(mut self, reloc: RelocMode) -> TargetMachineOptions {
+        self.reloc = reloc;
+        self
+    }
+
+    /// Sets the code model
+    pub fn with_code_model(mut self, code_model: CodeModel) -> TargetMachineOptions {
+        self.code_model = code_model;
+        self
+    }
+
+    /// Creates a target machine with these options
+    pub fn create_machine(&self) -> Result<TargetMachine, Error> {
+        TargetMachine::new_with_options(self)
+    }
+}
+
+impl Default for TargetMachineOptions {
+    fn default() -> TargetMachineOptions {
+        TargetMachineOptions::native()
+    }
+}
+
 /// A renamed `LLVMCodeGenFileType`
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
 pub enum FileType {
@@ -97,22 +718,23 @@ impl ByteOrdering {
 /// A wrapper around a `LLVMTargetRef`
 #[derive(Copy, Clone)]
 pub struct Target {
-    target: LLVMTargetRef,
+    pub(crate) target: LLVMTargetRef,
 }
 
 impl Target {
     /// Attempts to create a `Target` using the given triple
-    pub fn from_triple(triple: String) -> Result<Target, String> {
+    pub fn from_triple(triple: &Triple) -> Result<Target, Error> {
+        initialize()?;
+        let triple = into_c(triple)?;
         unsafe {
-            initialize();
             let mut target: LLVMTargetRef = null_mut();
             let mut error = null_mut();
             if LLVMGetTargetFromTriple(
-                    into_c(triple).as_ptr(),
+                    triple.as_ptr(),
                     &mut target as *mut LLVMTargetRef,
                     &mut error as *mut *mut i8,
                 ) == 1 || target.is_null() {
-                Err(from_c(error).unwrap_or(String::new()))
+                Err(Error::Target(take_message(error)))
             } else {
                 Ok(Target {
                     target,
@@ -121,26 +743,65 @@ impl Target {
         }
     }
 
+    /// Returns an iterator over the targets registered with LLVM
+    ///
+    /// Only the targets selected by the cargo features are registered. With the default
+    /// `all-targets` feature this is every target compiled into LLVM, otherwise it is only the
+    /// targets of the enabled architecture features and `native`.
+    pub fn all() -> iter::Targets {
+        // Any targets that were initialized can still be listed
+        let _ = initialize();
+        iter::Targets {
+            target: unsafe {
+                LLVMGetFirstTarget()
+            },
+        }
+    }
+
+    /// Attempts to find the target with the given name, such as `x86-64`
+    pub fn from_name<S>(name: S) -> Result<Target, Error> where S: AsRef<str> {
+        initialize()?;
+        let name = name.as_ref();
+        let target = unsafe {
+            LLVMGetTargetFromName(into_c(name)?.as_ptr())
+        };
+        if target.is_null() {
+            Err(Error::Target(format!("no target named {}", name)))
+        } else {
+            Ok(Target {
+                target,
+            })
+        }
+    }
+
     /// Creates a target machine with the default options
-    pub fn create_machine(&self, triple: String) -> TargetMachine {
-        self.create_machine_with_options(triple, "generic".to_owned(), String::new(), OptLevel::Default)
+    pub fn create_machine(&self, triple: &Triple) -> Result<TargetMachine, Error> {
+        self.create_machine_with_options(&TargetMachineOptions::new(triple.clone()))
     }
 
     /// Creates a target machine with the given options
-    pub fn create_machine_with_options(&self, triple: String, cpu: String, features: String,
-                                       level: OptLevel) -> TargetMachine {
-        TargetMachine {
-            machine: unsafe {
-                LLVMCreateTargetMachine(
-                    self.target,
-                    into_c(triple).as_ptr(),
-                    into_c(cpu).as_ptr(),
-                    into_c(features).as_ptr(),
-                    level.inner(),
-                    LLVMRelocMode::LLVMRelocDefault,
-                    LLVMCodeModel::LLVMCodeModelDefault,
-                )
-            }
+    pub fn create_machine_with_options(&self, options: &TargetMachineOptions) -> Result<TargetMachine, Error> {
+        let triple = into_c(&options.triple)?;
+        let cpu = into_c(options.cpu.as_str())?;
+        let features = into_c(options.features.as_str())?;
+        let machine = unsafe {
+            LLVMCreateTargetMachine(
+                self.target,
+                triple.as_ptr(),
+                cpu.as_ptr(),
+                features.as_ptr(),
+                options.level.inner(),
+                options.reloc.inner(),
+                options.code_model.inner(),
+            )
+        };
+        if machine.is_null() {
+            Err(Error::Target(format!("could not create a target machine for {}", self.name())))
+        } else {
+            Ok(TargetMachine {
+                machine: Rc::new(OwnedTargetMachine(machine)),
+                options: Arc::new(options.clone()),
+            })
         }
     }
 
@@ -151,6 +812,27 @@ impl Target {
         }
     }
 
+    /// Returns true if this target supports JIT compilation
+    pub fn has_jit(&self) -> bool {
+        unsafe {
+            LLVMTargetHasJIT(self.target) != 0
+        }
+    }
+
+    /// Returns true if a target machine can be created for this target
+    pub fn has_target_machine(&self) -> bool {
+        unsafe {
+            LLVMTargetHasTargetMachine(self.target) != 0
+        }
+    }
+
+    /// Returns true if this target has an assembly backend
+    pub fn has_asm_backend(&self) -> bool {
+        unsafe {
+            LLVMTargetHasAsmBackend(self.target) != 0
+        }
+    }
+
     /// Gets the description of this target
     pub fn description(&self) -> String {
         unsafe {
@@ -171,58 +853,112 @@ impl Display for Target {
     }
 }
 
-/// A wrapper around a `LLVMTargetMachineRef`
+/// Disposes a `LLVMTargetMachineRef` once every `TargetMachine` sharing it has been dropped
+struct OwnedTargetMachine(LLVMTargetMachineRef);
+
+impl Drop for OwnedTargetMachine {
+    fn drop(&mut self) {
+        unsafe {
+            LLVMDisposeTargetMachine(self.0);
+        }
+    }
+}
+
+/// A reference counted wrapper around a `LLVMTargetMachineRef`
+///
+/// Clones share the same machine, which is disposed when the last clone leaves scope. A machine
+/// can't be sent to another thread, but its `options` can, to create an equivalent machine there.
 #[derive(Clone)]
 pub struct TargetMachine {
-    machine: LLVMTargetMachineRef,
+    machine: Rc<OwnedTargetMachine>,
+    options: Arc<TargetMachineOptions>,
 }
 
 impl TargetMachine {
     /// Creates a target machine with the native target triple and options
-    pub fn native() -> Result<TargetMachine, String> {
-        TargetMachine::new(default_triple())
+    pub fn native() -> Result<TargetMachine, Error> {
+        TargetMachine::new(&default_triple())
     }
 
     /// Creates a target machine with the default options
-    pub fn new(triple: String) -> Result<TargetMachine, String> {
-        Ok(Target::from_triple(triple.clone())?.create_machine(triple))
+    pub fn new(triple: &Triple) -> Result<TargetMachine, Error> {
+        Target::from_triple(triple)?.create_machine(triple)
     }
 
     /// Creates a target machine with the given options
-    pub fn new_with_options(triple: String, cpu: String, features: String,
-                            level: OptLevel) -> Result<TargetMachine, String> {
-        Ok(Target::from_triple(triple.clone())?.create_machine_with_options(triple, cpu, features, level))
+    pub fn new_with_options(options: &TargetMachineOptions) -> Result<TargetMachine, Error> {
+        Target::from_triple(&options.triple)?.create_machine_with_options(options)
     }
 
     /// Emits code for a module to a given file with the given file type
-    pub fn emit_module_to_file<P>(&self, module: &Module, file: P, file_type: FileType) -> Result<(), String>
+    pub fn emit_module_to_file<P>(&self, module: &Module, file: P, file_type: FileType) -> Result<(), Error>
         where P: AsRef<Path> {
+        initialize_asm_printers()?;
+        self.check_layout(module)?;
+        let file = path_to_c(file.as_ref())?;
         unsafe {
-            LLVM_InitializeAllAsmPrinters();
-            let file_str = into_c(file.as_ref().to_str().expect("invalid path")).into_raw();
             let mut error = null_mut();
-            let flag = LLVMTargetMachineEmitToFile(
-                self.machine,
+            if LLVMTargetMachineEmitToFile(
+                self.inner(),
                 module.module.unwrap(),
-                file_str,
+                file.as_ptr() as *mut i8,
                 file_type.inner(),
                 &mut error as *mut *mut i8,
-            ) == 1;
-            CString::from_raw(file_str);
-            if flag {
-                Err(from_c(error).unwrap_or(String::new()))
+            ) == 1 {
+                Err(Error::Target(take_message(error)))
             } else {
                 Ok(())
             }
         }
     }
 
+    /// Emits code for a module into memory with the given file type
+    pub fn emit_module_to_memory(&self, module: &Module, file_type: FileType) -> Result<Vec<u8>, Error> {
+        initialize_asm_printers()?;
+        self.check_layout(module)?;
+        unsafe {
+            let mut error = null_mut();
+            let mut buffer = null_mut();
+            if LLVMTargetMachineEmitToMemoryBuffer(
+                self.inner(),
+                module.module.unwrap(),
+                file_type.inner(),
+                &mut error as *mut *mut i8,
+                &mut buffer,
+            ) == 1 {
+                Err(Error::Target(take_message(error)))
+            } else {
+                Ok(MemoryBuffer::from_inner(buffer).as_bytes().to_vec())
+            }
+        }
+    }
+
+    /// Emits assembly for a module into a string
+    pub fn emit_assembly_to_string(&self, module: &Module) -> Result<String, Error> {
+        let bytes = self.emit_module_to_memory(module, FileType::Assembly)?;
+        Ok(String::from_utf8_lossy(&bytes).into_owned())
+    }
+
+    /// Checks that the data layout of a module, if it has one, is compatible with this machine
+    fn check_layout(&self, module: &Module) -> Result<(), Error> {
+        if module.data_layout_str().is_empty() {
+            return Ok(());
+        }
+        let (layout, module_layout) = (self.data_layout(), module.data_layout());
+        if module_layout.is_compatible_with(&layout) {
+            Ok(())
+        } else {
+            Err(Error::Target(format!(
+                "module data layout {:?} does not match the target machine layout {:?}",
+                module_layout.to_string(), layout.to_string(),
+            )))
+        }
+    }
+
     /// Creates a data layout based on this target machine
     pub fn data_layout(&self) -> TargetData {
-        TargetData {
-            data: unsafe {
-                LLVMCreateTargetDataLayout(self.machine)
-            }
+        unsafe {
+            TargetData::from_inner(LLVMCreateTargetDataLayout(self.inner()))
         }
     }
 
@@ -230,29 +966,66 @@ impl TargetMachine {
     pub fn target(&self) -> Target {
         Target {
             target: unsafe{
-                LLVMGetTargetMachineTarget(self.machine)
+                LLVMGetTargetMachineTarget(self.inner())
             }
         }
     }
 
     /// Gets the target triple of this target machine
-    pub fn triple(&self) -> String {
-        unsafe {
-            from_c(LLVMGetTargetMachineTriple(self.machine)).unwrap_or(String::new())
-        }
+    pub fn triple(&self) -> Triple {
+        Triple::normalized(&unsafe {
+            take_message(LLVMGetTargetMachineTriple(self.inner()))
+        })
     }
 
     /// Gets the cpu of this target machine
     pub fn cpu(&self) -> String {
         unsafe {
-            from_c(LLVMGetTargetMachineCPU(self.machine)).unwrap_or(String::new())
+            from_c(LLVMGetTargetMachineCPU(self.inner())).unwrap_or(String::new())
         }
     }
 
     /// Gets the features of this target machine
     pub fn features(&self) -> String {
         unsafe {
-            from_c(LLVMGetTargetMachineFeatureString(self.machine)).unwrap_or(String::new())
+            from_c(LLVMGetTargetMachineFeatureString(self.inner())).unwrap_or(String::new())
+        }
+    }
+
+    /// Returns the options this target machine was created with
+    ///
+    /// The options can be shared between threads, unlike the machine itself.
+    pub fn options(&self) -> Arc<TargetMachineOptions> {
+        self.options.clone()
+    }
+
+    /// Returns true if this target machine is shared with a clone
+    pub fn is_shared(&self) -> bool {
+        Rc::strong_count(&self.machine) > 1
+    }
+
+    /// Returns the internal target machine reference
+    pub fn inner(&self) -> LLVMTargetMachineRef {
+        self.machine.0
+    }
+
+    /// Destroys the wrapper, returning the internal target machine reference
+    ///
+    /// If the machine is shared with a clone, a new machine is created with the same options so
+    /// that the returned reference is owned by the caller.
+    ///
+    /// # Safety
+    ///
+    /// The machine is no longer disposed automatically, so the caller must dispose it.
+    pub unsafe fn into_inner(self) -> Result<LLVMTargetMachineRef, Error> {
+        let target = self.target();
+        match Rc::try_unwrap(self.machine) {
+            Ok(machine) => {
+                let inner = machine.0;
+                mem::forget(machine);
+                Ok(inner)
+            },
+            Err(_) => target.create_machine_with_options(&self.options)?.into_inner(),
         }
     }
 }
@@ -263,27 +1036,78 @@ impl Debug for TargetMachine {
     }
 }
 
-impl Drop for TargetMachine {
+/// Disposes a `LLVMTargetDataRef` once every `TargetData` sharing it has been dropped
+struct OwnedTargetData(LLVMTargetDataRef);
+
+impl Drop for OwnedTargetData {
     fn drop(&mut self) {
         unsafe {
-            LLVMDisposeTargetMachine(self.machine);
+            LLVMDisposeTargetData(self.0);
         }
     }
 }
 
-/// A wrapper around a `LLVMTargetDataRef`
+/// A reference counted wrapper around a `LLVMTargetDataRef`
+///
+/// Clones share the same data layout, which is disposed when the last clone leaves scope.
 #[derive(Clone)]
 pub struct TargetData {
-    pub(crate) data: LLVMTargetDataRef,
+    data: Rc<OwnedTargetData>,
 }
 
 impl TargetData {
+    /// Creates a data layout from a layout string, such as `e-m:e-i64:64-n8:16:32:64-S128`
+    ///
+    /// The string is checked before it is passed to LLVM, which aborts on invalid layouts.
+    pub fn parse<S>(layout: S) -> Result<TargetData, Error> where S: AsRef<str> {
+        let layout = layout.as_ref();
+        layout_specs(layout).map_err(|message| {
+            Error::Target(format!("invalid data layout {:?}: {}", layout, message))
+        })?;
+        let layout = into_c(layout)?;
+        Ok(unsafe {
+            TargetData::from_inner(LLVMCreateTargetData(layout.as_ptr()))
+        })
+    }
+
+    /// Takes ownership of a data layout created by LLVM
+    ///
+    /// # Safety
+    ///
+    /// The data layout must be valid and must not be owned by anything else, such as a target
+    /// machine, since it is disposed when the last clone is dropped.
+    pub unsafe fn from_inner(data: LLVMTargetDataRef) -> TargetData {
+        TargetData {
+            data: Rc::new(OwnedTargetData(data)),
+        }
+    }
+
+    /// Returns the internal data layout reference
+    pub fn inner(&self) -> LLVMTargetDataRef {
+        self.data.0
+    }
+
+    /// Returns true if code for this layout can be used with the other layout
+    ///
+    /// The layouts must have the same byte order and pointer size, and every specification that
+    /// is given in both layouts must match.
+    pub fn is_compatible_with(&self, other: &TargetData) -> bool {
+        if self.byte_order() != other.byte_order() || self.size_of_ptr() != other.size_of_ptr() {
+            return false;
+        }
+        let (specs, other_specs) = match (layout_specs(&self.to_string()), layout_specs(&other.to_string())) {
+            (Ok(specs), Ok(other_specs)) => (specs, other_specs),
+            _ => return false,
+        };
+        specs.iter().all(|(key, spec)| other_specs.get(key).map_or(true, |other_spec| spec == other_spec))
+    }
+
     /// Returns the byte order of this data layout
     pub fn byte_order(&self) -> ByteOrdering {
         unsafe {
             use llvm_sys::target::LLVMByteOrdering::*;
             use self::ByteOrdering::*;
-            match LLVMByteOrder(self.data) {
+            match LLVMByteOrder(self.inner()) {
                 LLVMBigEndian => BigEndian,
                 LLVMLittleEndian => LittleEndian,
             }
@@ -293,74 +1117,87 @@ impl TargetData {
     /// Returns the size of a pointer
     pub fn size_of_ptr(&self) -> u64 {
         unsafe {
-            LLVMPointerSize(self.data) as u64
+            LLVMPointerSize(self.inner()) as u64
         }
     }
 
     /// Returns the size of a pointer in bits
     pub fn bit_size_of_ptr(&self) -> u64 {
         unsafe {
-            LLVMPointerSize(self.data) as u64 * 8
+            LLVMPointerSize(self.inner()) as u64 * 8
         }
     }
 
     /// Returns the byte offset of an element in a struct
     pub fn offset_of_element(&self, ty: Type, index: u32) -> u64 {
         unsafe {
-            LLVMOffsetOfElement(self.data, ty.ty, index)
+            LLVMOffsetOfElement(self.inner(), ty.ty, index)
         }
     }
 
     /// Returns the element at a byte offset in a struct
     pub fn element_at_offset(&self, ty: Type, offset: u64) -> u32 {
         unsafe {
-            LLVMElementAtOffset(self.data, ty.ty, offset)
+            LLVMElementAtOffset(self.inner(), ty.ty, offset)
         }
     }
 
     /// Returns the size of a type
     pub fn size_of(&self, ty: Type) -> u64 {
         unsafe {
-            LLVMABISizeOfType(self.data, ty.ty)
+            LLVMABISizeOfType(self.inner(), ty.ty)
         }
     }
 
     /// Returns the size of a type in bits
     pub fn bit_size_of(&self, ty: Type) -> u64 {
         unsafe {
-            LLVMSizeOfTypeInBits(self.data, ty.ty)
+            LLVMSizeOfTypeInBits(self.inner(), ty.ty)
         }
     }
 
     /// Returns the size of a type when stored
     pub fn store_size_of(&self, ty: Type) -> u64 {
         unsafe {
-            LLVMStoreSizeOfType(self.data, ty.ty)
+            LLVMStoreSizeOfType(self.inner(), ty.ty)
         }
     }
 
     /// Returns the ABI alignment of a type
     pub fn abi_alignment_of(&self, ty: Type) -> u32 {
         unsafe {
-            LLVMABIAlignmentOfType(self.data, ty.ty)
+            LLVMABIAlignmentOfType(self.inner(), ty.ty)
         }
     }
 
     /// Returns the ABI alignment of a type
     pub fn call_frame_alignment_of(&self, ty: Type) -> u32 {
         unsafe {
-            LLVMCallFrameAlignmentOfType(self.data, ty.ty)
+            LLVMCallFrameAlignmentOfType(self.inner(), ty.ty)
         }
     }
 
     /// Returns the preferred alignment of a type
     pub fn preferred_alignment_of(&self, ty: Type) -> u32 {
         unsafe {
-            LLVMPreferredAlignmentOfType(self.data, ty.ty)
+            LLVMPreferredAlignmentOfType(self.inner(), ty.ty)
+        }
+    }
+}
+
+impl PartialEq for TargetData {
+    /// Layouts are equal if they contain the same specifications, regardless of their order
+    fn eq(&self, other: &TargetData) -> bool {
+        let (layout, other_layout) = (self.to_string(), other.to_string());
+        match (layout_specs(&layout), layout_specs(&other_layout)) {
+            (Ok(specs), Ok(other_specs)) => specs == other_specs,
+            _ => layout == other_layout,
         }
     }
 }
 
+impl Eq for TargetData {}
+
 impl Debug for TargetData {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "TargetData({:?})", self.to_string())
@@ -370,15 +1207,236 @@ impl Debug for TargetData {
 impl Display for TargetData {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "{}", unsafe {
-            from_c(LLVMCopyStringRepOfTargetData(self.data)).unwrap_or(String::new())
+            take_message(LLVMCopyStringRepOfTargetData(self.inner()))
         })
     }
 }
 
-impl Drop for TargetData {
-    fn drop(&mut self) {
-        unsafe {
-            LLVMDisposeTargetData(self.data);
+
+/// Splits a data layout string into its specifications, keyed by what they specify
+///
+/// Returns a message describing the problem if the layout isn't valid. The checks mirror those in
+/// `DataLayout::parseSpecifier`, since LLVM aborts on invalid layouts instead of returning an error.
+/// Later specifications replace earlier ones with the same key, as they do in LLVM.
+fn layout_specs(layout: &str) -> Result<BTreeMap<String, String>, String> {
+    fn number(field: &str) -> Result<u32, String> {
+        if field.is_empty() || !field.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(format!("expected a number, found {:?}", field));
+        }
+        match field.parse() {
+            Ok(number) if number < 1 << 24 => Ok(number),
+            _ => Err(format!("number {} does not fit in 24 bits", field)),
+        }
+    }
+
+    fn alignments(spec: &str, fields: &[&str], aggregate: bool) -> Result<(), String> {
+        if fields.is_empty() || fields.len() > 2 {
+            return Err(format!("{:?} must have an ABI alignment and an optional preferred alignment", spec));
+        }
+        let abi = number(fields[0])?;
+        let preferred = match fields.get(1) {
+            Some(field) => number(field)?,
+            None => abi,
+        };
+        if abi == 0 && !aggregate {
+            Err(format!("ABI alignment in {:?} must be nonzero", spec))
+        } else if abi % 8 != 0 || preferred % 8 != 0 {
+            Err(format!("alignments in {:?} must be multiples of 8", spec))
+        } else if abi != 0 && (!abi.is_power_of_two() || !preferred.is_power_of_two()) {
+            Err(format!("alignments in {:?} must be powers of 2", spec))
+        } else if abi / 8 >= 1 << 16 || preferred / 8 >= 1 << 16 {
+            Err(format!("alignments in {:?} must be less than 2^16 bytes", spec))
+        } else if preferred < abi {
+            Err(format!("preferred alignment in {:?} is less than the ABI alignment", spec))
+        } else {
+            Ok(())
+        }
+    }
+
+    let mut specs = BTreeMap::new();
+    if layout.is_empty() {
+        return Ok(specs);
+    }
+    for spec in layout.split('-') {
+        let mut fields = spec.split(':');
+        let first = fields.next().unwrap();
+        let fields: Vec<&str> = fields.collect();
+        let key = if first.is_empty() {
+            return Err("empty specification".to_owned());
+        } else if first == "e" || first == "E" {
+            if !fields.is_empty() {
+                return Err(format!("unexpected fields in {:?}", spec));
+            }
+            "e".to_owned()
+        } else if first == "ni" {
+            for field in &fields {
+                if number(field)? == 0 {
+                    return Err("address space 0 cannot be non-integral".to_owned());
+                }
+            }
+            first.to_owned()
+        } else if first == "m" {
+            match fields.as_slice() {
+                &["e"] | &["m"] | &["o"] | &["w"] | &["x"] => first.to_owned(),
+                _ => return Err(format!("unknown mangling in {:?}", spec)),
+            }
+        } else {
+            if !first.is_char_boundary(1) {
+                return Err(format!("unknown specification {:?}", spec));
+            }
+            let (kind, size) = first.split_at(1);
+            match kind {
+                "p" => {
+                    let address_space = if size.is_empty() { 0 } else { number(size)? };
+                    if fields.is_empty() || number(fields[0])? == 0 {
+                        return Err(format!("{:?} must have a nonzero pointer size", spec));
+                    }
+                    if number(fields[0])? % 8 != 0 {
+                        return Err(format!("pointer size in {:?} must be a multiple of 8", spec));
+                    }
+                    alignments(spec, &fields[1..], false)?;
+                    format!("p{}", address_space)
+                },
+                "i" | "v" | "f" => {
+                    if number(size)? == 0 {
+                        return Err(format!("{:?} must have a nonzero size", spec));
+                    }
+                    alignments(spec, &fields, false)?;
+                    format!("{}{}", kind, number(size)?)
+                },
+                "a" => {
+                    if !size.is_empty() && number(size)? != 0 {
+                        return Err(format!("{:?} must not have a size", spec));
+                    }
+                    alignments(spec, &fields, true)?;
+                    kind.to_owned()
+                },
+                "n" => {
+                    for field in Some(size).into_iter().chain(fields.iter().cloned()) {
+                        if number(field)? == 0 {
+                            return Err(format!("native integer widths in {:?} must be nonzero", spec));
+                        }
+                    }
+                    kind.to_owned()
+                },
+                "S" | "A" => {
+                    let value = number(size)?;
+                    if !fields.is_empty() || (kind == "S" && value % 8 != 0) {
+                        return Err(format!("invalid specification {:?}", spec));
+                    }
+                    kind.to_owned()
+                },
+                _ => return Err(format!("unknown specification {:?}", spec)),
+            }
+        };
+        specs.insert(key, spec.to_owned());
+    }
+    Ok(specs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn normalize(triple: &str) -> String {
+        Triple::normalized(triple).as_str().to_owned()
+    }
+
+    #[test]
+    fn normalize_fills_missing_components() {
+        assert_eq!(normalize("x86_64-linux-gnu"), "x86_64-unknown-linux-gnu");
+        assert_eq!(normalize("x86_64--linux"), "x86_64-unknown-linux");
+        assert_eq!(normalize("wasm32"), "wasm32-unknown-unknown");
+        assert_eq!(normalize(""), "unknown-unknown-unknown");
+    }
+
+    #[test]
+    fn normalize_keeps_unrecognized_vendors() {
+        assert_eq!(normalize("arm-none-eabi"), "arm-none-unknown-eabi");
+        assert_eq!(normalize("thumbv7em-none-eabihf"), "thumbv7em-none-unknown-eabihf");
+        assert_eq!(normalize("x86_64-unknown-linux-gnu"), "x86_64-unknown-linux-gnu");
+        assert_eq!(normalize("x86_64-apple-darwin17.0.0"), "x86_64-apple-darwin17.0.0");
+    }
+
+    #[test]
+    fn normalize_moves_components() {
+        assert_eq!(normalize("linux-x86_64"), "x86_64-unknown-linux");
+        assert_eq!(normalize("x86_64-gnu-linux"), "x86_64-unknown-linux-gnu");
+        assert_eq!(normalize("pc-i686-linux"), "i686-pc-linux");
+    }
+
+    #[test]
+    fn normalize_windows() {
+        assert_eq!(normalize("i686-pc-win32"), "i686-pc-windows-msvc");
+        assert_eq!(normalize("x86_64-pc-windows"), "x86_64-pc-windows-msvc");
+        assert_eq!(normalize("i386-mingw32"), "i386-unknown-windows-gnu");
+        assert_eq!(normalize("i686-pc-cygwin"), "i686-pc-windows-cygnus");
+        assert_eq!(normalize("i686-pc-windows-elf"), "i686-pc-windows-elf");
+        assert_eq!(normalize("i686-pc-windows-gnu-elf"), "i686-pc-windows-gnu-elf");
+        assert_eq!(normalize("i686-pc-cygwin-elf"), "i686-pc-windows-cygnus-elf");
+    }
+
+    #[test]
+    fn normalize_environments() {
+        assert_eq!(normalize("arm-linux-androideabi"), "arm-unknown-linux-android");
+        assert_eq!(normalize("arm-linux-androideabi21"), "arm-unknown-linux-android21");
+        assert_eq!(normalize("armv7-suse-linux-gnueabi"), "armv7-suse-linux-gnueabihf");
+    }
+
+    #[test]
+    fn components() {
+        let triple = Triple::parse("armv7-none-eabi").unwrap();
+        assert_eq!(triple.arch(), "armv7");
+        assert_eq!(triple.vendor(), "none");
+        assert_eq!(triple.os(), "unknown");
+        assert_eq!(triple.environment(), Some("eabi"));
+        assert_eq!(triple.pointer_width(), Some(32));
+        assert!(Triple::parse("").is_err());
+        assert!(Triple::from_parts("x86_64", "pc", "win dows", None).is_err());
+    }
+
+    #[test]
+    fn pointer_widths() {
+        let width = |triple: &str| Triple::parse(triple).unwrap().pointer_width();
+        assert_eq!(width("avr-unknown-unknown"), Some(16));
+        assert_eq!(width("i686-pc-linux-gnu"), Some(32));
+        assert_eq!(width("mipsallegrexel-unknown-linux"), Some(32));
+        assert_eq!(width("renderscript32-unknown-linux"), Some(32));
+        assert_eq!(width("thumbv7em-none-eabi"), Some(32));
+        assert_eq!(width("arm64-apple-ios"), Some(64));
+        assert_eq!(width("aarch64_be-unknown-linux-gnu"), Some(64));
+        assert_eq!(width("spir64-unknown-unknown"), Some(64));
+        assert_eq!(width("x86-unknown-linux"), None);
+        assert_eq!(width("powerpcle-unknown-linux"), None);
+        assert!(Triple::parse("x86_64-apple-macosx10.12").unwrap().is_macos());
+        assert!(Triple::parse("x86_64-apple-macos10.14").unwrap().is_macos());
+    }
+
+    #[test]
+    fn layout_specs_accepts_valid_layouts() {
+        let specs = layout_specs("e-m:e-p:64:64-i64:64-f80:128-n8:16:32:64-S128").unwrap();
+        assert_eq!(specs.get("p0").map(String::as_str), Some("p:64:64"));
+        assert_eq!(specs.get("i64").map(String::as_str), Some("i64:64"));
+        assert_eq!(specs.get("n").map(String::as_str), Some("n8:16:32:64"));
+        assert!(layout_specs("").unwrap().is_empty());
+        assert!(layout_specs("E-p1:32:32:64-a:0:64-v128:128-ni:1-A5").is_ok());
+    }
+
+    #[test]
+    fn layout_specs_later_specs_replace_earlier_ones() {
+        let specs = layout_specs("e-i64:32-E-i64:64").unwrap();
+        assert_eq!(specs.get("e").map(String::as_str), Some("E"));
+        assert_eq!(specs.get("i64").map(String::as_str), Some("i64:64"));
+    }
+
+    #[test]
+    fn layout_specs_rejects_layouts_llvm_aborts_on() {
+        for layout in &[
+            "p:4:8", "p:65:64", "p:0:64", "p:64", "p16777216:64:64", "i16777216:64", "i32:12",
+            "i32:24", "i32:64:32", "i32:0", "i32:1048576", "a8:64", "n0", "S12", "m:q", "ni:0",
+            "x", "e-", "e:1", "i32:abc",
+        ] {
+            assert!(layout_specs(layout).is_err(), "{:?} should be rejected", layout);
         }
     }
 }
\ No newline at end of file
@@ -1,126 +1,165 @@
 //! A wrapper around a `LLVMTypeRef`
 
 use super::*;
-use super::types::*;
+use super::c_api::*;
 
+use llvm_sys::LLVMTypeKind::*;
 use std::mem;
 
 /// A wrapper around a `LLVMTypeRef` for a specific context
 #[derive(Copy, Clone)]
-pub struct Type {
-    pub(crate) ty: LLVMTypeRef
+pub struct Type<'ctx> {
+    pub(crate) ty: LLVMTypeRef,
+    pub(crate) marker: PhantomData<&'ctx Context>,
 }
 
-impl Type {
+impl<'ctx> Type<'ctx> {
     /// Creates a function that returns this type
-    pub fn function(&self, params: Vec<Type>, va_args: bool) -> Type {
-        Type {
+    pub fn function(&self, params: Vec<Type<'ctx>>, va_args: bool) -> Result<Type<'ctx>, Error> {
+        Ok(Type {
             ty: unsafe {
-                LLVMFunctionType(self.ty, ty_vec(&params).as_mut_ptr(), params.len() as u32, va_args as i32)
-            }
-        }
+                let mut params = ty_vec(&params);
+                for &param in &params {
+                    check_context(LLVMGetTypeContext(self.ty), param)?;
+                }
+                LLVMFunctionType(self.ty, params.as_mut_ptr(), params.len() as u32, va_args as i32)
+            },
+            marker: PhantomData,
+        })
     }
 
     /// Creates a pointer to this type
-    pub fn pointer(&self) -> Type {
+    pub fn pointer(&self) -> Type<'ctx> {
         Type {
             ty: unsafe {
                 LLVMPointerType(self.ty, 0)
-            }
+            },
+            marker: PhantomData,
         }
     }
 
     /// The internal reference counter
-    pub fn rc(&self) -> Type {
-        ty_struct(vec![*self, ty_i32()], false)
+    pub fn rc(&self) -> Type<'ctx> {
+        Type {
+            ty: unsafe {
+                let context = LLVMGetTypeContext(self.ty);
+                LLVMStructTypeInContext(context, vec![self.ty, LLVMInt32TypeInContext(context)].as_mut_ptr(), 2, 0)
+            },
+            marker: PhantomData,
+        }
     }
 
     /// Set the body of a struct
-    pub fn struct_set_body(&self, elements: Vec<Type>, packed: bool) {
+    pub fn struct_set_body(&self, elements: Vec<Type<'ctx>>, packed: bool) -> Result<(), Error> {
         unsafe {
-            LLVMStructSetBody(self.ty, ty_vec(&elements).as_mut_ptr(), elements.len() as u32, packed as i32)
-        }
-    }
-
-    /// An integer constant of this type
-    pub fn const_int(&self, val: u64) -> Value {
-        Value {
-            value: unsafe {
-                LLVMConstInt(self.ty, val, 0)
-            }
-        }
-    }
-
-    /// An integer constant of this type
-    pub fn const_signed_int(&self, val: i64) -> Value {
-        Value {
-            value: unsafe {
-                LLVMConstInt(self.ty, mem::transmute(val), 0)
-            }
-        }
-    }
-
-    /// A real constant of this type
-    pub fn const_real(&self, val: f64) -> Value {
-        Value {
-            value: unsafe {
-                LLVMConstReal(self.ty, val)
+            let mut elements = ty_vec(&elements);
+            for &element in &elements {
+                check_context(LLVMGetTypeContext(self.ty), element)?;
             }
+            LLVMStructSetBody(self.ty, elements.as_mut_ptr(), elements.len() as u32, packed as i32);
         }
+        Ok(())
+    }
+
+    /// An integer constant of this type, which must be an integer type
+    pub fn const_int(&self, val: u64) -> Result<IntValue<'ctx>, Error> {
+        self.check_kind(&[LLVMIntegerTypeKind], "an integer")?;
+        Ok(IntValue {
+            base: unsafe {
+                value(LLVMConstInt(self.ty, val, 0))
+            },
+        })
+    }
+
+    /// An integer constant of this type, which must be an integer type
+    pub fn const_signed_int(&self, val: i64) -> Result<IntValue<'ctx>, Error> {
+        self.check_kind(&[LLVMIntegerTypeKind], "an integer")?;
+        Ok(IntValue {
+            base: unsafe {
+                value(LLVMConstInt(self.ty, mem::transmute(val), 0))
+            },
+        })
+    }
+
+    /// A real constant of this type, which must be a floating point type
+    pub fn const_real(&self, val: f64) -> Result<FloatValue<'ctx>, Error> {
+        self.check_kind(&[
+            LLVMHalfTypeKind, LLVMFloatTypeKind, LLVMDoubleTypeKind,
+            LLVMX86_FP80TypeKind, LLVMFP128TypeKind, LLVMPPC_FP128TypeKind,
+        ], "a floating point")?;
+        Ok(FloatValue {
+            base: unsafe {
+                value(LLVMConstReal(self.ty, val))
+            },
+        })
     }
 
     /// A constant named struct with the given elements
-    pub fn const_struct(&self, elements: Vec<Value>) -> Value {
-        Value {
+    pub fn const_struct(&self, elements: Vec<Value<'ctx>>) -> Result<Value<'ctx>, Error> {
+        Ok(Value {
             value: unsafe {
-                LLVMConstNamedStruct(self.ty, val_vec(&elements).as_mut_ptr(), elements.len() as u32)
-            }
-        }
+                let mut elements = val_vec(&elements);
+                for &element in &elements {
+                    check_context(LLVMGetTypeContext(self.ty), LLVMTypeOf(element))?;
+                }
+                LLVMConstNamedStruct(self.ty, elements.as_mut_ptr(), elements.len() as u32)
+            },
+            marker: PhantomData,
+        })
     }
 
     /// A constant array with the given elements
-    pub fn const_array(&self, elements: Vec<Value>) -> Value {
-        Value {
+    pub fn const_array(&self, elements: Vec<Value<'ctx>>) -> Result<Value<'ctx>, Error> {
+        Ok(Value {
             value: unsafe {
-                LLVMConstArray(self.ty, val_vec(&elements).as_mut_ptr(), elements.len() as u32)
-            }
-        }
+                let mut elements = val_vec(&elements);
+                for &element in &elements {
+                    check_context(LLVMGetTypeContext(self.ty), LLVMTypeOf(element))?;
+                }
+                LLVMConstArray(self.ty, elements.as_mut_ptr(), elements.len() as u32)
+            },
+            marker: PhantomData,
+        })
     }
 
     /// The `undef` value for this type
-    pub fn undef(&self) -> Value {
+    pub fn undef(&self) -> Value<'ctx> {
         Value {
             value: unsafe {
                 LLVMGetUndef(self.ty)
-            }
+            },
+            marker: PhantomData,
         }
     }
 
     /// The `null` value for this type
-    pub fn null(&self) -> Value {
+    pub fn null(&self) -> Value<'ctx> {
         Value {
             value: unsafe {
                 LLVMConstNull(self.ty)
-            }
+            },
+            marker: PhantomData,
         }
     }
 
     /// The all ones value for this type
-    pub fn ones(&self) -> Value {
+    pub fn ones(&self) -> Value<'ctx> {
         Value {
             value: unsafe {
                 LLVMConstAllOnes(self.ty)
-            }
+            },
+            marker: PhantomData,
         }
     }
 
-    /// The `null` value for this pointer type
-    pub fn null_ptr(&self) -> Value {
-        Value {
-            value: unsafe {
-                LLVMConstPointerNull(self.ty)
-            }
-        }
+    /// The `null` value for this type, which must be a pointer type
+    pub fn null_ptr(&self) -> Result<PointerValue<'ctx>, Error> {
+        self.check_kind(&[LLVMPointerTypeKind], "a pointer")?;
+        Ok(PointerValue {
+            base: unsafe {
+                value(LLVMConstPointerNull(self.ty))
+            },
+        })
     }
 
     /// Dump the contents of the type to stderr
@@ -134,9 +173,20 @@ impl Type {
     pub fn inner(&self) -> LLVMTypeRef {
         self.ty
     }
+
+    /// Checks that this type is one of the given kinds
+    pub(crate) fn check_kind(&self, kinds: &[LLVMTypeKind], expected: &str) -> Result<(), Error> {
+        unsafe {
+            if kinds.contains(&LLVMGetTypeKind(self.ty)) {
+                Ok(())
+            } else {
+                Err(Error::InvalidType(format!("expected {} type, found {}", expected, take_message(LLVMPrintTypeToString(self.ty)))))
+            }
+        }
+    }
 }
 
-impl Deref for Type {
+impl<'ctx> Deref for Type<'ctx> {
     type Target = LLVMTypeRef;
 
     fn deref(&self) -> &LLVMTypeRef {
@@ -144,8 +194,26 @@ impl Deref for Type {
     }
 }
 
-impl Debug for Type {
+impl<'ctx> Debug for Type<'ctx> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "Type")
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn constants_check_type_kind() {
+        let context = Context::new();
+        assert!(context.ty_i32().const_int(1).is_ok());
+        assert!(context.ty_i8().const_signed_int(-1).is_ok());
+        assert!(context.ty_half().const_real(0.5).is_ok());
+        assert!(context.ty_i8().pointer().null_ptr().is_ok());
+        assert!(context.ty_double().const_int(1).is_err());
+        assert!(context.ty_i32().const_real(0.5).is_err());
+        assert!(context.ty_i32().null_ptr().is_err());
+        assert!(context.ty_void().const_signed_int(0).is_err());
+    }
 }
\ No newline at end of file
@@ -1,148 +1,83 @@
 //! Provides functions that return LLVM types in the global context
 
 use super::*;
-use super::c_api::*;
 
 /// Create a named struct type
-pub fn create_named_struct<S>(name: S) -> Type where S: AsRef<str> {
-    Type {
-        ty: unsafe {
-            LLVMStructCreateNamed(context(), into_c(name).as_ptr())
-        }
-    }
+pub fn create_named_struct<S>(name: S) -> Result<Type<'static>, Error> where S: AsRef<str> {
+    Context::global().create_named_struct(name)
 }
 
 /// A array type with a certain number of elements
-pub fn ty_array(ty: Type, count: u32) -> Type {
-    Type {
-        ty: unsafe {
-            LLVMArrayType(ty.ty, count)
-        }
-    }
+pub fn ty_array(ty: Type<'static>, count: u32) -> Result<Type<'static>, Error> {
+    Context::global().ty_array(ty, count)
 }
 
 /// A struct type with the given elements
-pub fn ty_struct(elements: Vec<Type>, packed: bool) -> Type {
-    Type {
-        ty: unsafe {
-            LLVMStructTypeInContext(context(), ty_vec(&elements).as_mut_ptr(), elements.len() as u32, packed as i32)
-        }
-    }
+pub fn ty_struct(elements: Vec<Type<'static>>, packed: bool) -> Result<Type<'static>, Error> {
+    Context::global().ty_struct(elements, packed)
 }
 
 /// The `void` type
-pub fn ty_void() -> Type {
-    Type {
-        ty: unsafe {
-            LLVMVoidTypeInContext(context())
-        }
-    }
+pub fn ty_void() -> Type<'static> {
+    Context::global().ty_void()
 }
 
 /// The `i1` type
-pub fn ty_i1() -> Type {
-    Type {
-        ty: unsafe {
-            LLVMInt1TypeInContext(context())
-        }
-    }
+pub fn ty_i1() -> Type<'static> {
+    Context::global().ty_i1()
 }
 
 /// The `i8` type
-pub fn ty_i8() -> Type {
-    Type {
-        ty: unsafe {
-            LLVMInt8TypeInContext(context())
-        }
-    }
+pub fn ty_i8() -> Type<'static> {
+    Context::global().ty_i8()
 }
 
 /// The `i16` type
-pub fn ty_i16() -> Type {
-    Type {
-        ty: unsafe {
-            LLVMInt16TypeInContext(context())
-        }
-    }
+pub fn ty_i16() -> Type<'static> {
+    Context::global().ty_i16()
 }
 
 /// The `i32` type
-pub fn ty_i32() -> Type {
-    Type {
-        ty: unsafe {
-            LLVMInt32TypeInContext(context())
-        }
-    }
+pub fn ty_i32() -> Type<'static> {
+    Context::global().ty_i32()
 }
 
 /// The `i64` type
-pub fn ty_i64() -> Type {
-    Type {
-        ty: unsafe {
-            LLVMInt64TypeInContext(context())
-        }
-    }
+pub fn ty_i64() -> Type<'static> {
+    Context::global().ty_i64()
 }
 
 /// The `i128` type
-pub fn ty_i128() -> Type {
-    Type {
-        ty: unsafe {
-            LLVMInt128TypeInContext(context())
-        }
-    }
+pub fn ty_i128() -> Type<'static> {
+    Context::global().ty_i128()
 }
 
 /// The `isize` type for a given data layout
-pub fn ty_isize(data: &target::TargetData) -> Type {
-    Type {
-        ty: unsafe {
-            llvm_sys::target::LLVMIntPtrTypeInContext(context(), data.data)
-        }
-    }
+pub fn ty_isize(data: &target::TargetData) -> Type<'static> {
+    Context::global().ty_isize(data)
 }
 
 /// An integer type with any number of bits
-pub fn ty_i(bits: u32) -> Type {
-    Type {
-        ty: unsafe {
-            LLVMIntTypeInContext(context(), bits)
-        }
-    }
+pub fn ty_i(bits: u32) -> Type<'static> {
+    Context::global().ty_i(bits)
 }
 
 /// The `half` type
-pub fn ty_half() -> Type {
-    Type {
-        ty: unsafe {
-            LLVMHalfTypeInContext(context())
-        }
-    }
+pub fn ty_half() -> Type<'static> {
+    Context::global().ty_half()
 }
 
 /// The `float` type
-pub fn ty_float() -> Type {
-    Type {
-        ty: unsafe {
-            LLVMFloatTypeInContext(context())
-        }
-    }
+pub fn ty_float() -> Type<'static> {
+    Context::global().ty_float()
 }
 
 /// The `double` type
-pub fn ty_double() -> Type {
-    Type {
-        ty: unsafe {
-            LLVMDoubleTypeInContext(context())
-        }
-    }
+pub fn ty_double() -> Type<'static> {
+    Context::global().ty_double()
 }
 
 /// The `fp128` type
-pub fn ty_fp128() -> Type {
-    Type {
-        ty: unsafe {
-            LLVMFP128TypeInContext(context())
-        }
-    }
+pub fn ty_fp128() -> Type<'static> {
+    Context::global().ty_fp128()
 }
\ No newline at end of file
@@ -4,67 +4,16 @@ use super::*;
 use super::c_api::*;
 
 /// A wrapper around a `LLVMValueRef` for a specific context
+///
+/// Values that belong to a module, such as functions, globals and instructions, borrow the module
+/// and cannot outlive it. Constants only borrow their context.
 #[derive(Copy, Clone)]
-pub struct Value {
-    pub(crate) value: LLVMValueRef
+pub struct Value<'ctx> {
+    pub(crate) value: LLVMValueRef,
+    pub(crate) marker: PhantomData<&'ctx Context>,
 }
 
-impl Value {
-    /// Adds a block to this function
-    pub fn append_basic_block<S>(&self, name: S) -> BasicBlock where S: AsRef<str> {
-        BasicBlock {
-            basic_block: unsafe {
-                LLVMAppendBasicBlockInContext(context(), self.value, CString::new(name.as_ref()).expect("invalid module name").as_ptr() as *const i8)
-            }
-        }
-    }
-
-    /// Delete this function
-    pub fn delete_function(self) {
-        unsafe {
-            LLVMDeleteFunction(self.value)
-        }
-    }
-
-    /// Delete this global
-    pub fn delete_global(self) {
-        unsafe {
-            LLVMDeleteGlobal(self.value)
-        }
-    }
-
-    /// Set the calling convention of this function
-    pub fn set_call_conv(&self, cc: CallConv) -> Value {
-        unsafe {
-            LLVMSetFunctionCallConv(self.value, cc as u32);
-        }
-        *self
-    }
-
-    /// Set the linkage of this global
-    pub fn set_linkage(&self, link: Linkage) -> Value {
-        unsafe {
-            LLVMSetLinkage(self.value, link.inner());
-        }
-        *self
-    }
-
-    /// Set whether this is a tail call
-    pub fn set_tail_call(&self, tail: bool) -> Value {
-        unsafe {
-            LLVMSetTailCall(self.value, tail as i32);
-        }
-        *self
-    }
-
-    /// Set whether this global is a constant
-    pub fn set_global_const(&self, constant: bool) -> Value {
-        unsafe {
-            LLVMSetGlobalConstant(self.value, constant as i32);
-        }
-        *self
-    }
-
+impl<'ctx> Value<'ctx> {
     /// Returns true if this value is a constant
     pub fn is_constant(&self) -> bool {
         unsafe {
@@ -79,75 +28,29 @@ impl Value {
         }
     }
 
-    /// Set whether the address of this global is significant
-    pub fn set_unnamed_addr(&self, unnamed_addr: bool) -> Value {
-        unsafe {
-            LLVMSetUnnamedAddr(self.value, unnamed_addr as i32);
-        }
-        *self
-    }
-
-    /// Set the initializer of this global
-    pub fn set_global_initializer(&self, init: Value) -> Value {
-        unsafe {
-            LLVMSetInitializer(self.value, init.value);
-        }
-        *self
-    }
-
     /// Set the alignment of this value
-    pub fn set_alignment(&self, bytes: u32) -> Value {
+    pub fn set_alignment(&self, bytes: u32) -> Value<'ctx> {
         unsafe {
             LLVMSetAlignment(self.value, bytes);
         }
         *self
     }
 
-    /// Get a parameter for this function
-    pub fn param(&self, param: u32) -> Value {
-        Value {
-            value: unsafe {
-                LLVMGetParam(self.value, param as u32)
-            }
-        }
-    }
-
-    /// Returns an iterator over all parameters in this function
-    pub fn params(&self) -> iter::Params {
-        iter::Params {
-            pointer: Value {
-                value: unsafe {
-                    LLVMGetFirstParam(self.value)
-                }
-            }
-        }
-    }
-
     /// Set the alignment of this parameter
-    pub fn set_param_alignment(&self, bytes: u32) -> Value {
+    pub fn set_param_alignment(&self, bytes: u32) -> Value<'ctx> {
         unsafe {
             LLVMSetParamAlignment(self.value, bytes);
         }
         *self
     }
 
-    /// Returns an iterator over all basic blocks in this function
-    pub fn blocks(&self) -> iter::Blocks {
-        iter::Blocks {
-            pointer: BasicBlock {
-                basic_block: unsafe {
-                    LLVMGetFirstBasicBlock(self.value)
-                }
-            }
-        }
-    }
-
     /// Set the name of a value
-    pub fn name<S>(&self, name: S) -> Value where S: AsRef<str> {
+    pub fn name<S>(&self, name: S) -> Result<Value<'ctx>, Error> where S: AsRef<str> {
+        let name = into_c(name)?;
         unsafe {
-            LLVMSetValueName(self.value, into_c(name).as_ptr());
+            LLVMSetValueName(self.value, name.as_ptr());
         }
-        *self
+        Ok(*self)
     }
 
     /// Get the name of a value
@@ -158,11 +61,12 @@ impl Value {
     }
 
     /// Get the type of this value
-    pub fn ty(&self) -> Type {
+    pub fn ty(&self) -> Type<'ctx> {
         Type {
             ty: unsafe {
                 LLVMTypeOf(self.value)
-            }
+            },
+            marker: PhantomData,
         }
     }
 
@@ -179,7 +83,7 @@ impl Value {
     }
 }
 
-impl Deref for Value {
+impl<'ctx> Deref for Value<'ctx> {
     type Target = LLVMValueRef;
 
     fn deref(&self) -> &LLVMValueRef {
@@ -187,7 +91,7 @@ impl Deref for Value {
     }
 }
 
-impl Debug for Value {
+impl<'ctx> Debug for Value<'ctx> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         if let Some(name) = self.get_name() {
             write!(f, "Value({})", name)
@@ -0,0 +1,377 @@
+//! Strongly typed wrappers around a `LLVMValueRef`
+//!
+//! A `Value` can be converted into one of these wrappers with `TryFrom`, which checks that the
+//! value really is of that kind, and converted back with `From`. Every wrapper dereferences into a
+//! `Value`, so the untyped methods are still available.
+
+use super::*;
+use super::c_api::*;
+
+use std::convert::TryFrom;
+use llvm_sys::LLVMTypeKind::*;
+use llvm_sys::analysis::*;
+use std::ptr::null_mut;
+
+macro_rules! value_wrapper {
+    ($(#[$attr:meta])* pub struct $name:ident; |$value:ident| $check:expr) => {
+        $(#[$attr])*
+        #[derive(Copy, Clone)]
+        pub struct $name<'ctx> {
+            pub(crate) base: Value<'ctx>,
+        }
+
+        impl<'ctx> $name<'ctx> {
+            /// Set the name of this value
+            pub fn name<S>(&self, name: S) -> Result<$name<'ctx>, Error> where S: AsRef<str> {
+                self.base.name(name)?;
+                Ok(*self)
+            }
+        }
+
+        impl<'ctx> TryFrom<Value<'ctx>> for $name<'ctx> {
+            type Error = Value<'ctx>;
+
+            fn try_from(value: Value<'ctx>) -> Result<$name<'ctx>, Value<'ctx>> {
+                let $value = value.value;
+                if !$value.is_null() && unsafe { $check } {
+                    Ok($name {
+                        base: value,
+                    })
+                } else {
+                    Err(value)
+                }
+            }
+        }
+
+        impl<'ctx> From<$name<'ctx>> for Value<'ctx> {
+            fn from(value: $name<'ctx>) -> Value<'ctx> {
+                value.base
+            }
+        }
+
+        impl<'ctx> Deref for $name<'ctx> {
+            type Target = Value<'ctx>;
+
+            fn deref(&self) -> &Value<'ctx> {
+                &self.base
+            }
+        }
+
+        impl<'ctx> Debug for $name<'ctx> {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                if let Some(name) = self.get_name() {
+                    write!(f, "{}({})", stringify!($name), name)
+                } else {
+                    write!(f, "{}", stringify!($name))
+                }
+            }
+        }
+    };
+}
+
+value_wrapper! {
+    /// A value with an integer type
+    pub struct IntValue;
+    |value| LLVMGetTypeKind(LLVMTypeOf(value)) == LLVMIntegerTypeKind
+}
+
+value_wrapper! {
+    /// A value with a floating point type
+    pub struct FloatValue;
+    |value| match LLVMGetTypeKind(LLVMTypeOf(value)) {
+        LLVMHalfTypeKind | LLVMFloatTypeKind | LLVMDoubleTypeKind |
+        LLVMX86_FP80TypeKind | LLVMFP128TypeKind | LLVMPPC_FP128TypeKind => true,
+        _ => false,
+    }
+}
+
+value_wrapper! {
+    /// A value with a pointer type
+    pub struct PointerValue;
+    |value| LLVMGetTypeKind(LLVMTypeOf(value)) == LLVMPointerTypeKind
+}
+
+value_wrapper! {
+    /// A function in a module
+    pub struct FunctionValue;
+    |value| !LLVMIsAFunction(value).is_null()
+}
+
+value_wrapper! {
+    /// A global variable in a module
+    pub struct GlobalValue;
+    |value| !LLVMIsAGlobalVariable(value).is_null()
+}
+
+value_wrapper! {
+    /// An instruction in a basic block
+    pub struct InstructionValue;
+    |value| !LLVMIsAInstruction(value).is_null()
+}
+
+value_wrapper! {
+    /// A phi instruction
+    pub struct PhiValue;
+    |value| !LLVMIsAPHINode(value).is_null()
+}
+
+value_wrapper! {
+    /// A basic block used as a value
+    pub struct BasicBlockValue;
+    |value| LLVMValueIsBasicBlock(value) != 0
+}
+
+impl<'ctx> FunctionValue<'ctx> {
+    /// Adds a block to this function
+    pub fn append_basic_block<S>(&self, name: S) -> Result<BasicBlock<'ctx>, Error> where S: AsRef<str> {
+        let name = into_c(name)?;
+        Ok(BasicBlock {
+            basic_block: unsafe {
+                LLVMAppendBasicBlockInContext(LLVMGetTypeContext(LLVMTypeOf(self.value)), self.value, name.as_ptr())
+            },
+            marker: PhantomData,
+        })
+    }
+
+    /// Delete this function
+    ///
+    /// Use `Module::delete_function` to have the requirements below checked.
+    ///
+    /// # Safety
+    ///
+    /// The function must not be used, such as by a call. Any other copies of this handle, and of
+    /// handles to values or blocks inside the function, must not be used afterwards.
+    pub unsafe fn delete_function(self) {
+        LLVMDeleteFunction(self.value)
+    }
+
+    /// Set the calling convention of this function
+    pub fn set_call_conv(&self, cc: CallConv) -> FunctionValue<'ctx> {
+        unsafe {
+            LLVMSetFunctionCallConv(self.value, cc as u32);
+        }
+        *self
+    }
+
+    /// Set the linkage of this function
+    pub fn set_linkage(&self, link: Linkage) -> FunctionValue<'ctx> {
+        unsafe {
+            LLVMSetLinkage(self.value, link.inner());
+        }
+        *self
+    }
+
+    /// Set whether the address of this function is significant
+    pub fn set_unnamed_addr(&self, unnamed_addr: bool) -> FunctionValue<'ctx> {
+        unsafe {
+            LLVMSetUnnamedAddr(self.value, unnamed_addr as i32);
+        }
+        *self
+    }
+
+    /// Verify that this function is well formed, returning the problems found if it isn't
+    ///
+    /// LLVM only reports messages for whole modules, so the diagnostics may also include problems
+    /// in other functions of the same module. If `view_cfg` is set, the control flow graph of the
+    /// function is displayed when it fails verification.
+    pub fn verify_function(&self, view_cfg: bool) -> Result<(), Error> {
+        unsafe {
+            if LLVMVerifyFunction(self.value, LLVMVerifierFailureAction::LLVMReturnStatusAction) == 0 {
+                return Ok(());
+            }
+            if view_cfg {
+                LLVMViewFunctionCFG(self.value);
+            }
+            let mut message = null_mut();
+            LLVMVerifyModule(
+                LLVMGetGlobalParent(self.value),
+                LLVMVerifierFailureAction::LLVMReturnStatusAction,
+                &mut message,
+            );
+            let mut diagnostics = Diagnostic::from_verifier(&take_message(message));
+            if diagnostics.is_empty() {
+                diagnostics.push(Diagnostic::from_message(
+                    format!("function {} is broken", self.get_name().unwrap_or(String::new()))
+                ));
+            }
+            Err(Error::Verify(diagnostics))
+        }
+    }
+
+    /// Finds the function with the same name in another module, such as a copy of this one
+    pub fn counterpart_in<'m, 'a>(&self, module: &'m Module<'a>) -> Option<FunctionValue<'m>> {
+        module.get_function(self.get_name()?)
+    }
+
+    /// Display the control flow graph of this function
+    pub fn view_cfg(&self) {
+        unsafe {
+            LLVMViewFunctionCFG(self.value);
+        }
+    }
+
+    /// Display the control flow graph of this function without the contents of its blocks
+    pub fn view_cfg_only(&self) {
+        unsafe {
+            LLVMViewFunctionCFGOnly(self.value);
+        }
+    }
+
+    /// Get a parameter for this function, if it has that many
+    pub fn param(&self, param: u32) -> Option<Value<'ctx>> {
+        unsafe {
+            if param < LLVMCountParams(self.value) {
+                Some(Value {
+                    value: LLVMGetParam(self.value, param),
+                    marker: PhantomData,
+                })
+            } else {
+                None
+            }
+        }
+    }
+
+    /// Returns an iterator over all parameters in this function
+    pub fn params(&self) -> iter::Params<'ctx> {
+        iter::Params {
+            pointer: Value {
+                value: unsafe {
+                    LLVMGetFirstParam(self.value)
+                },
+                marker: PhantomData,
+            }
+        }
+    }
+
+    /// Returns an iterator over all basic blocks in this function
+    pub fn blocks(&self) -> iter::Blocks<'ctx> {
+        iter::Blocks {
+            pointer: BasicBlock {
+                basic_block: unsafe {
+                    LLVMGetFirstBasicBlock(self.value)
+                },
+                marker: PhantomData,
+            }
+        }
+    }
+}
+
+impl<'ctx> GlobalValue<'ctx> {
+    /// Delete this global
+    ///
+    /// Use `Module::delete_global` to have the requirements below checked.
+    ///
+    /// # Safety
+    ///
+    /// The global must not be used. Any other copies of this handle must not be used afterwards.
+    pub unsafe fn delete_global(self) {
+        LLVMDeleteGlobal(self.value)
+    }
+
+    /// Finds the global with the same name in another module, such as a copy of this one
+    pub fn counterpart_in<'m, 'a>(&self, module: &'m Module<'a>) -> Option<GlobalValue<'m>> {
+        module.get_global(self.get_name()?)
+    }
+
+    /// Set the linkage of this global
+    pub fn set_linkage(&self, link: Linkage) -> GlobalValue<'ctx> {
+        unsafe {
+            LLVMSetLinkage(self.value, link.inner());
+        }
+        *self
+    }
+
+    /// Set whether this global is a constant
+    pub fn set_global_const(&self, constant: bool) -> GlobalValue<'ctx> {
+        unsafe {
+            LLVMSetGlobalConstant(self.value, constant as i32);
+        }
+        *self
+    }
+
+    /// Set whether the address of this global is significant
+    pub fn set_unnamed_addr(&self, unnamed_addr: bool) -> GlobalValue<'ctx> {
+        unsafe {
+            LLVMSetUnnamedAddr(self.value, unnamed_addr as i32);
+        }
+        *self
+    }
+
+    /// Set the initializer of this global
+    pub fn set_global_initializer<V>(&self, init: V) -> Result<GlobalValue<'ctx>, Error> where V: Into<Value<'ctx>> {
+        unsafe {
+            let init = init.into().value;
+            check_context(LLVMGetTypeContext(LLVMTypeOf(self.value)), LLVMTypeOf(init))?;
+            LLVMSetInitializer(self.value, init);
+        }
+        Ok(*self)
+    }
+}
+
+impl<'ctx> InstructionValue<'ctx> {
+    /// Set whether this is a tail call
+    pub fn set_tail_call(&self, tail: bool) -> InstructionValue<'ctx> {
+        unsafe {
+            LLVMSetTailCall(self.value, tail as i32);
+        }
+        *self
+    }
+}
+
+impl<'ctx> PhiValue<'ctx> {
+    /// Add values that are taken from certain blocks
+    pub fn add_incoming(&self, incoming: Vec<(Value<'ctx>, BasicBlock<'ctx>)>) -> Result<PhiValue<'ctx>, Error> {
+        let len = incoming.len();
+        let mut values = Vec::new();
+        let mut blocks = Vec::new();
+        for (val, block) in incoming {
+            values.push(val.value);
+            blocks.push(block.basic_block);
+        }
+        unsafe {
+            let context = LLVMGetTypeContext(LLVMTypeOf(self.value));
+            for (&val, &block) in values.iter().zip(&blocks) {
+                check_context(context, LLVMTypeOf(val))?;
+                check_context(context, LLVMTypeOf(LLVMBasicBlockAsValue(block)))?;
+            }
+            LLVMAddIncoming(self.value, values.as_mut_ptr(), blocks.as_mut_ptr(), len as u32);
+        }
+        Ok(*self)
+    }
+}
+
+impl<'ctx> BasicBlockValue<'ctx> {
+    /// Get the basic block this value represents
+    pub fn block(&self) -> BasicBlock<'ctx> {
+        BasicBlock {
+            basic_block: unsafe {
+                LLVMValueAsBasicBlock(self.value)
+            },
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<'ctx> From<FunctionValue<'ctx>> for PointerValue<'ctx> {
+    fn from(value: FunctionValue<'ctx>) -> PointerValue<'ctx> {
+        PointerValue {
+            base: value.base,
+        }
+    }
+}
+
+impl<'ctx> From<GlobalValue<'ctx>> for PointerValue<'ctx> {
+    fn from(value: GlobalValue<'ctx>) -> PointerValue<'ctx> {
+        PointerValue {
+            base: value.base,
+        }
+    }
+}
+
+impl<'ctx> From<PhiValue<'ctx>> for InstructionValue<'ctx> {
+    fn from(value: PhiValue<'ctx>) -> InstructionValue<'ctx> {
+        InstructionValue {
+            base: value.base,
+        }
+    }
+}